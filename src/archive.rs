@@ -0,0 +1,51 @@
+//! Finished tasks can be archived, which moves them out of the list into an
+//! archive kept next to it by the store. Besides archiving tasks by hand,
+//! tasks that have been finished for `archive_days` days are archived when
+//! their list is opened, once that setting is turned on in the config.
+//!
+//! Archived tasks keep their ids, so they can be moved back into the list
+//! as they were. Tasks depending on them see them as finished meanwhile.
+
+use chrono::{DateTime, Utc};
+
+use crate::{dates, Error, Task};
+
+/// Checks that `task` may be archived, which only finished tasks may
+pub fn ensure_archivable(task: &Task) -> Result<(), Error> {
+    if task.state.is_finished() {
+        Ok(())
+    } else {
+        Err(Error::NotFinished(task.id))
+    }
+}
+
+/// Ids of the tasks among `tasks` that were finished more than `days` days
+/// before `now`
+pub fn expired(tasks: &[Task], days: u64, now: DateTime<Utc>) -> Vec<usize> {
+    tasks
+        .iter()
+        .filter(|task| task.state.is_finished() && !task.is_deleted())
+        .filter(|task| {
+            task.finished_at
+                .is_some_and(|at| dates::expired(at, days, now))
+        })
+        .map(|task| task.id)
+        .collect()
+}
+
+/// Whether `task` matches the search `query`, ignoring case. Every word of
+/// the query has to appear in the name, the project, a tag or the notes.
+pub fn matches(task: &Task, query: &str) -> bool {
+    let haystack = [
+        task.name.as_str(),
+        task.project.as_deref().unwrap_or_default(),
+        &task.tags.join(" "),
+        &task.notes,
+    ]
+    .join("\n")
+    .to_lowercase();
+
+    query
+        .split_whitespace()
+        .all(|word| haystack.contains(&word.to_lowercase()))
+}
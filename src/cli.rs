@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::output::{Field, Format};
+
+/// Terminal UI for keeping track of tasks
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Task database file, defaults to db.json in the XDG data directory
+    #[arg(long, env = "TASK_TUI_DB", value_name = "PATH")]
+    pub db: Option<PathBuf>,
+
+    /// Named task list to open instead of the default one
+    #[arg(long, value_name = "NAME")]
+    pub list: Option<String>,
+
+    /// Restore the Nth most recent backup of the JSON database and exit
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "1")]
+    pub restore_backup: Option<usize>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Add a new task and print its id
+    Add {
+        /// Name of the task
+        #[arg(value_parser = parse_name)]
+        name: String,
+        /// Id of the task to add the new one as a subtask of
+        #[arg(long, value_name = "ID")]
+        parent: Option<usize>,
+    },
+    /// List all tasks
+    List {
+        /// Only list tasks in this state of the workflow, e.g. in-progress.
+        /// Case, spaces, `-` and `_` in the name do not matter.
+        #[arg(long)]
+        state: Option<String>,
+
+        /// List the tasks in the trash instead
+        #[arg(long, conflicts_with = "archived")]
+        trash: bool,
+
+        /// List the archived tasks instead
+        #[arg(long)]
+        archived: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+
+        /// Comma separated columns to print, defaults to all of them
+        #[arg(long, value_enum, value_delimiter = ',')]
+        fields: Vec<Field>,
+    },
+    /// Move a task on to its next state
+    Progress {
+        /// Id of the task
+        id: usize,
+    },
+    /// Move a task back to its previous state
+    Regress {
+        /// Id of the task
+        id: usize,
+    },
+    /// Move a task to any state
+    SetState {
+        /// Id of the task
+        id: usize,
+        /// State of the workflow to move it to, e.g. in-progress
+        state: String,
+    },
+    /// Mark a task as done
+    Done {
+        /// Id of the task
+        id: usize,
+    },
+    /// Move a task to the trash
+    Rm {
+        /// Id of the task
+        id: usize,
+        /// Delete the task for good instead, whether it is in the trash or not
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Take a task out of the trash
+    Restore {
+        /// Id of the task
+        id: usize,
+    },
+    /// Move a finished task to the archive
+    Archive {
+        /// Id of the task
+        id: usize,
+    },
+    /// Move a task out of the archive, back into the list
+    Unarchive {
+        /// Id of the task
+        id: usize,
+    },
+    /// Set the tasks a task depends on, or clear them when none are given
+    Depend {
+        /// Id of the task
+        id: usize,
+        /// Ids of the tasks that have to be done before it can be started
+        depends_on: Vec<usize>,
+    },
+    /// Print all details of a task
+    Show {
+        /// Id of the task
+        id: usize,
+    },
+    /// Import the JSON task database into the SQLite database
+    Migrate {
+        /// JSON file to import, defaults to the default JSON database
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+}
+
+/// Task names must not be blank, a nameless task would be an empty row
+fn parse_name(name: &str) -> Result<String, String> {
+    if name.trim().is_empty() {
+        Err(String::from("the task name must not be blank"))
+    } else {
+        Ok(name.to_string())
+    }
+}
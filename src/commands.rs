@@ -0,0 +1,236 @@
+use std::{convert::TryFrom, io};
+
+use crate::{
+    archive,
+    cli::Command,
+    dependencies,
+    output::{self, Format},
+    store::{Change, TaskStore},
+    subtasks, tags, trash, Error, Task, TaskState,
+};
+
+fn print_task_line(task: &Task) {
+    println!(
+        "{:>4}  {:<12} {}",
+        task.id,
+        task.state.to_string(),
+        task.name
+    );
+}
+
+fn print_task_details(task: &Task) {
+    println!("id:          {}", task.id);
+    println!("name:        {}", task.name);
+    println!("state:       {}", task.state);
+    println!("priority:    {}", task.priority);
+    if let Some(project) = &task.project {
+        println!("project:     {}", project);
+    }
+    if let Some(parent_id) = task.parent_id {
+        println!("parent:      {}", parent_id);
+    }
+    if !task.depends_on.is_empty() {
+        println!("depends on:  {}", dependencies::format(&task.depends_on));
+    }
+    if !task.tags.is_empty() {
+        println!("tags:        {}", tags::format(&task.tags));
+    }
+    println!("created at:  {}", task.created_at);
+    if let Some(started_at) = task.started_at {
+        println!("started at:  {}", started_at);
+    }
+    if let Some(finished_at) = task.finished_at {
+        println!("finished at: {}", finished_at);
+    }
+    if let Some(due_at) = task.due_at {
+        println!("due at:      {}", due_at);
+    }
+    if !task.history.is_empty() {
+        println!("history:");
+        for transition in &task.history {
+            println!(
+                "  {}  {} -> {}",
+                transition.at, transition.from, transition.to
+            );
+        }
+    }
+    if let Some(deleted_at) = task.deleted_at {
+        println!("deleted at:  {}", deleted_at);
+    }
+    if !task.notes.is_empty() {
+        println!("notes:");
+        for line in task.notes.lines() {
+            println!("  {}", line);
+        }
+    }
+}
+
+/// Tasks that are not in the trash
+fn live_tasks(store: &dyn TaskStore) -> Result<Vec<Task>, Error> {
+    let mut tasks = store.list()?;
+    tasks.retain(|task| !task.is_deleted());
+    Ok(tasks)
+}
+
+/// Task with the given id, which must not be in the trash
+fn find_task(store: &dyn TaskStore, id: usize) -> Result<Task, Error> {
+    store
+        .get(id)?
+        .filter(|task| !task.is_deleted())
+        .ok_or(Error::TaskNotFound(id))
+}
+
+fn find_task_in_trash(store: &dyn TaskStore, id: usize) -> Result<Task, Error> {
+    store
+        .get(id)?
+        .filter(Task::is_deleted)
+        .ok_or(Error::TaskNotFound(id))
+}
+
+/// Moves the task with the given id to another state through `change` and
+/// writes it back, together with its subtasks which are completed once it is
+/// finished
+fn change_state(
+    store: &mut dyn TaskStore,
+    id: usize,
+    change: impl FnOnce(&mut Task, &[Task]) -> Result<(), Error>,
+) -> Result<Task, Error> {
+    let mut tasks = live_tasks(store)?;
+    let mut task = find_task(store, id)?;
+    let was_finished = task.state.is_finished();
+    change(&mut task, &tasks)?;
+
+    let mut changes = vec![Change::Update(task.clone())];
+    if task.state.is_finished() && !was_finished {
+        let completed = subtasks::complete_descendants(&mut tasks, id)?;
+        changes.extend(completed.into_iter().map(Change::Update));
+    }
+    store.apply_all(changes)?;
+    Ok(task)
+}
+
+/// Runs a non-interactive subcommand against `store`
+pub fn run(command: Command, store: &mut dyn TaskStore) -> Result<(), Error> {
+    match command {
+        Command::Add { name, parent } => {
+            let mut task = Task::create_task(0, name);
+            if let Some(parent_id) = parent {
+                task.project = find_task(store, parent_id)?.project;
+                task.parent_id = Some(parent_id);
+            }
+            let id = store.insert(task)?;
+            println!("{}", id);
+        }
+        Command::List {
+            state,
+            trash,
+            archived,
+            format,
+            fields,
+        } => {
+            let state = state.as_deref().map(TaskState::try_from).transpose()?;
+            let tasks = if archived {
+                store.archived()?
+            } else {
+                store.list()?
+            };
+            let tasks: Vec<_> = tasks
+                .into_iter()
+                .filter(|task| task.is_deleted() == trash)
+                .filter(|task| state.as_ref().is_none_or(|state| &task.state == state))
+                .collect();
+
+            match format {
+                Format::Text if fields.is_empty() => tasks.iter().for_each(print_task_line),
+                _ => {
+                    let fields = if fields.is_empty() {
+                        output::ALL_FIELDS
+                    } else {
+                        &fields
+                    };
+                    output::write_tasks(&tasks, format, fields, io::stdout().lock())?;
+                }
+            }
+        }
+        Command::Progress { id } => {
+            let task = change_state(store, id, Task::progress)?;
+            print_task_line(&task);
+        }
+        Command::Regress { id } => {
+            let task = change_state(store, id, Task::regress)?;
+            print_task_line(&task);
+        }
+        Command::SetState { id, state } => {
+            let state = TaskState::try_from(state.as_str())?;
+            let task = change_state(store, id, |task, tasks| task.set_state(state, tasks))?;
+            print_task_line(&task);
+        }
+        Command::Done { id } => {
+            let task = change_state(store, id, |task, tasks| {
+                task.ensure_unblocked(tasks)?;
+                task.complete();
+                Ok(())
+            })?;
+            print_task_line(&task);
+        }
+        Command::Rm { id, purge: false } => {
+            let mut tasks = live_tasks(store)?;
+            find_task(store, id)?;
+            let changed = trash::trash(&mut tasks, id);
+            store.apply_all(changed.into_iter().map(Change::Update).collect())?;
+            print_task_line(&find_task_in_trash(store, id)?);
+        }
+        Command::Rm { id, purge: true } => {
+            let task = store.get(id)?.ok_or(Error::TaskNotFound(id))?;
+            if task.is_deleted() {
+                // A task in the trash is already cut off from the others
+                store.delete(id)?;
+            } else {
+                let mut tasks = store.list()?;
+                let mut changed = subtasks::promote_children(&mut tasks, id);
+                changed.extend(dependencies::remove_dependency(&mut tasks, id));
+                let mut changes: Vec<_> = changed.into_iter().map(Change::Update).collect();
+                changes.push(Change::Delete(id));
+                store.apply_all(changes)?;
+            }
+            print_task_line(&task);
+        }
+        Command::Restore { id } => {
+            let mut task = find_task_in_trash(store, id)?;
+            trash::restore(&live_tasks(store)?, &mut task);
+            store.update(task.clone())?;
+            print_task_line(&task);
+        }
+        Command::Archive { id } => {
+            let task = find_task(store, id)?;
+            archive::ensure_archivable(&task)?;
+            store.archive(id)?;
+            print_task_line(&task);
+        }
+        Command::Unarchive { id } => {
+            store.unarchive(id)?;
+            print_task_line(&find_task(store, id)?);
+        }
+        Command::Depend { id, depends_on } => {
+            let mut task = find_task(store, id)?;
+            dependencies::validate(&live_tasks(store)?, id, &depends_on)?;
+            task.depends_on = depends_on;
+            store.update(task.clone())?;
+            print_task_line(&task);
+        }
+        Command::Show { id } => {
+            let task = match store.get(id)? {
+                Some(task) => task,
+                None => store
+                    .archived()?
+                    .into_iter()
+                    .find(|task| task.id == id)
+                    .ok_or(Error::TaskNotFound(id))?,
+            };
+            print_task_details(&task);
+        }
+        Command::Migrate { .. } => unreachable!("migrate does not operate on a single store"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::{fs, io, path::PathBuf};
+
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+
+use crate::{workflow::Workflow, Error};
+
+const CONFIG_PATH: &str = ".config/task-tui/config.json";
+
+/// Storage implementation used for the task list
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+/// Order the task list is shown in
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Id,
+    /// Highest priority first, then the earliest due date
+    Priority,
+}
+
+/// User settings read from `~/.config/task-tui/config.json`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub backend: Backend,
+    /// Number of previous versions of the JSON database kept as backups
+    pub backups: usize,
+    /// Milliseconds to wait after a change before writing it, so a burst of
+    /// keypresses results in a single write
+    pub save_delay_ms: u64,
+    /// Tasks due within this many hours are highlighted as due soon
+    pub due_soon_hours: u64,
+    /// Order of the task list on startup, it can be toggled in the UI
+    pub sort: SortOrder,
+    /// States tasks move through, see [`crate::workflow`]
+    pub workflow: Workflow,
+    /// Tasks in the trash for longer than this many days are purged when
+    /// their list is opened, 0 keeps them until they are purged by hand
+    pub trash_days: u64,
+    /// Tasks finished for longer than this many days are archived when their
+    /// list is opened, 0 (the default) leaves them in the list until archived
+    /// by hand
+    pub archive_days: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            backend: Backend::default(),
+            backups: 3,
+            save_delay_ms: 0,
+            due_soon_hours: 24,
+            sort: SortOrder::default(),
+            workflow: Workflow::default(),
+            trash_days: 30,
+            archive_days: 0,
+        }
+    }
+}
+
+fn find_config_file() -> Option<PathBuf> {
+    home::home_dir().map(|mut path| {
+        path.push(CONFIG_PATH);
+        path
+    })
+}
+
+impl Config {
+    /// How long before its due date a task counts as due soon
+    pub fn due_soon(&self) -> TimeDelta {
+        i64::try_from(self.due_soon_hours)
+            .ok()
+            .and_then(TimeDelta::try_hours)
+            .unwrap_or(TimeDelta::MAX)
+    }
+
+    /// Loads the config file, using the defaults when it does not exist
+    pub fn load() -> Result<Config, Error> {
+        let path = match find_config_file() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        let invalid = |message: String| Error::ConfigError {
+            path: path.display().to_string(),
+            message,
+        };
+        match fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).map_err(|e| invalid(e.to_string())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(invalid(e.to_string())),
+        }
+    }
+}
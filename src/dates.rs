@@ -0,0 +1,296 @@
+//! Due dates are entered and shown in the local timezone, but stored in UTC.
+//!
+//! Besides exact dates, input can be relative to the current day:
+//!
+//! - `today`, `tomorrow` and weekday names like `fri` or `friday`, meaning
+//!   the next such day
+//! - `next week`, `next month`, `next year` and `next fri`, the latter being
+//!   Friday of the following week
+//! - `in 3d`, `in 2 weeks`, `in 1mo`, `in 90 min`, `in 4h`
+//! - `eod`, `eow`, `eom` and `eoy` for the end of the day, week, month or year
+//!
+//! Any of these except for minute and hour offsets can be followed by a time
+//! like `14:00`, without one they mean the end of that day.
+
+use chrono::{
+    DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    TimeZone, Utc, Weekday,
+};
+
+/// Format due dates are shown in, and the most precise one accepted as input
+const DISPLAY_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Format of the resolved date shown while typing one
+const PREVIEW_FORMAT: &str = "%a %Y-%m-%d %H:%M";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Time of day a due date without one refers to
+fn end_of_day() -> NaiveTime {
+    NaiveTime::from_hms_opt(23, 59, 0).expect("valid time")
+}
+
+/// Interprets `naive` as local time. Times skipped by a DST change have no
+/// meaning and yield `None`.
+fn local_to_utc(naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|local| local.with_timezone(&Utc))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    Some(match word {
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "wednesday" => Weekday::Wed,
+        "thu" | "thur" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// Length of time named by a unit of `in <amount> <unit>`
+enum Unit {
+    Minutes,
+    Hours,
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+fn parse_unit(word: &str) -> Option<Unit> {
+    Some(match word {
+        "m" | "min" | "mins" | "minute" | "minutes" => Unit::Minutes,
+        "h" | "hr" | "hrs" | "hour" | "hours" => Unit::Hours,
+        "d" | "day" | "days" => Unit::Days,
+        "w" | "wk" | "wks" | "week" | "weeks" => Unit::Weeks,
+        "mo" | "month" | "months" => Unit::Months,
+        "y" | "yr" | "yrs" | "year" | "years" => Unit::Years,
+        _ => return None,
+    })
+}
+
+/// Splits the words following `in` into an amount and its unit, accepting
+/// both `3d` and `3 days`
+fn parse_offset(words: &[&str]) -> Option<(u32, Unit)> {
+    let (amount, unit) = match words {
+        [word] => {
+            let split = word.find(|c: char| !c.is_ascii_digit())?;
+            word.split_at(split)
+        }
+        [amount, unit] => (*amount, *unit),
+        _ => return None,
+    };
+    Some((amount.parse().ok()?, parse_unit(unit)?))
+}
+
+/// First day after `today` that falls on `weekday`
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    today + Days::new(if ahead == 0 { 7 } else { ahead.into() })
+}
+
+fn last_day_of_month(date: NaiveDate) -> Option<NaiveDate> {
+    let first = date.with_day(1)?;
+    first.checked_add_months(Months::new(1))?.pred_opt()
+}
+
+/// Resolves the words of a date expression relative to `today`
+fn parse_day(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    match words {
+        ["today"] | ["eod"] => Some(today),
+        ["tomorrow"] => today.checked_add_days(Days::new(1)),
+        ["eow"] => Some(today.week(Weekday::Mon).last_day()),
+        ["eom"] => last_day_of_month(today),
+        ["eoy"] => NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        ["next", "week"] => today.checked_add_days(Days::new(7)),
+        ["next", "month"] => today.checked_add_months(Months::new(1)),
+        ["next", "year"] => today.checked_add_months(Months::new(12)),
+        ["next", day] => {
+            let weekday = parse_weekday(day)?;
+            let next_monday = today.week(Weekday::Mon).last_day() + Days::new(1);
+            next_monday.checked_add_days(Days::new(weekday.num_days_from_monday().into()))
+        }
+        ["in", offset @ ..] => {
+            let (amount, unit) = parse_offset(offset)?;
+            match unit {
+                Unit::Days => today.checked_add_days(Days::new(amount.into())),
+                Unit::Weeks => today.checked_add_days(Days::new(u64::from(amount) * 7)),
+                Unit::Months => today.checked_add_months(Months::new(amount)),
+                Unit::Years => today.checked_add_months(Months::new(amount.checked_mul(12)?)),
+                Unit::Minutes | Unit::Hours => None,
+            }
+        }
+        [word] => match parse_weekday(word) {
+            Some(weekday) => Some(next_weekday(today, weekday)),
+            None => NaiveDate::parse_from_str(word, DATE_FORMAT).ok(),
+        },
+        _ => None,
+    }
+}
+
+/// Parses `input` as described in the module docs, relative to `now`
+fn parse_at(input: &str, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(input) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+
+    let input = input.to_lowercase();
+    let words: Vec<&str> = input.split_whitespace().collect();
+
+    // Offsets below a day are exact instead of meaning the end of a day
+    if let ["in", offset @ ..] = words.as_slice() {
+        let delta = match parse_offset(offset) {
+            Some((amount, Unit::Minutes)) => Some(TimeDelta::minutes(amount.into())),
+            Some((amount, Unit::Hours)) => Some(TimeDelta::hours(amount.into())),
+            _ => None,
+        };
+        if let Some(delta) = delta {
+            return now.with_timezone(&Utc).checked_add_signed(delta);
+        }
+    }
+
+    let (day_words, time) = match words.split_last() {
+        Some((last, rest)) => match NaiveTime::parse_from_str(last, TIME_FORMAT) {
+            Ok(time) => (rest, time),
+            Err(_) => (words.as_slice(), end_of_day()),
+        },
+        None => return None,
+    };
+
+    let today = now.date_naive();
+    let day = if day_words.is_empty() {
+        today
+    } else {
+        parse_day(day_words, today)?
+    };
+    local_to_utc(day.and_time(time))
+}
+
+/// Parses a due date entered by the user, see the module docs for the
+/// accepted formats
+pub fn parse(input: &str) -> Option<DateTime<Utc>> {
+    parse_at(input, Local::now())
+}
+
+pub fn format(timestamp: DateTime<Utc>) -> String {
+    timestamp
+        .with_timezone(&Local)
+        .format(DISPLAY_FORMAT)
+        .to_string()
+}
+
+/// Like [`format`], but including the day of the week
+pub fn preview(timestamp: DateTime<Utc>) -> String {
+    timestamp
+        .with_timezone(&Local)
+        .format(PREVIEW_FORMAT)
+        .to_string()
+}
+
+/// Whether `timestamp` lies more than `days` days before `now`, as for the
+/// retention settings in the config. Nothing is expired when `days` is 0.
+pub fn expired(timestamp: DateTime<Utc>, days: u64, now: DateTime<Utc>) -> bool {
+    match i64::try_from(days).ok().and_then(TimeDelta::try_days) {
+        Some(age) if days > 0 => now - timestamp > age,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday, 2026-10-14 09:30 local time
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 14, 9, 30, 0).unwrap()
+    }
+
+    fn local(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Local
+            .with_ymd_and_hms(2026, month, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn parse(input: &str) -> Option<DateTime<Utc>> {
+        parse_at(input, now())
+    }
+
+    #[test]
+    fn days_without_a_time_mean_their_end() {
+        assert_eq!(parse("today"), Some(local(10, 14, 23, 59)));
+        assert_eq!(parse("tomorrow"), Some(local(10, 15, 23, 59)));
+        assert_eq!(parse("eom"), Some(local(10, 31, 23, 59)));
+    }
+
+    #[test]
+    fn weekdays_mean_the_next_such_day() {
+        assert_eq!(parse("fri"), Some(local(10, 16, 23, 59)));
+        assert_eq!(parse("Friday"), Some(local(10, 16, 23, 59)));
+        assert_eq!(parse("wed"), Some(local(10, 21, 23, 59)));
+    }
+
+    #[test]
+    fn next_weekday_is_in_the_following_week() {
+        assert_eq!(parse("next fri"), Some(local(10, 23, 23, 59)));
+        assert_eq!(parse("next mon"), Some(local(10, 19, 23, 59)));
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse("in 3d"), Some(local(10, 17, 23, 59)));
+        assert_eq!(parse("in 3 days"), Some(local(10, 17, 23, 59)));
+        assert_eq!(parse("in 2 weeks"), Some(local(10, 28, 23, 59)));
+        assert_eq!(parse("in 90 min"), Some(local(10, 14, 11, 0)));
+        assert_eq!(parse("in 4h"), Some(local(10, 14, 13, 30)));
+    }
+
+    #[test]
+    fn exact_dates_and_times() {
+        assert_eq!(parse("2026-11-02 14:00"), Some(local(11, 2, 14, 0)));
+        assert_eq!(parse("2026-11-02"), Some(local(11, 2, 23, 59)));
+        assert_eq!(parse("tomorrow 14:00"), Some(local(10, 15, 14, 0)));
+        assert_eq!(parse("8:15"), Some(local(10, 14, 8, 15)));
+        assert_eq!(
+            parse("2026-11-02T14:00:00Z"),
+            Some(Utc.with_ymd_and_hms(2026, 11, 2, 14, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        for input in [
+            "",
+            "someday",
+            "next",
+            "next fortnight",
+            "in 3",
+            "in x days",
+            "in 3 lightyears",
+            "in 90 min 14:00",
+            "2026-13-01",
+            "2026-11-02 25:00",
+            "tomorrow tomorrow",
+        ] {
+            assert_eq!(parse(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn expired_after_the_given_days_unless_0() {
+        let now = now().with_timezone(&Utc);
+        let old = now - TimeDelta::try_days(15).unwrap();
+        assert!(expired(old, 14, now));
+        assert!(!expired(old, 15, now));
+        assert!(!expired(old, 0, now));
+        assert!(!expired(old, u64::MAX, now));
+    }
+}
@@ -0,0 +1,152 @@
+//! A task can depend on other tasks through `depends_on`, meaning it cannot
+//! leave the initial state of the workflow before all of them are finished.
+//! Until then the task is blocked.
+//!
+//! Dependencies must not form a cycle, or none of the tasks in it could ever
+//! be started. Dependencies on deleted tasks are dropped with them.
+
+use crate::{Error, Task};
+
+/// Ids of the dependencies of `task` that are not finished yet
+pub fn blockers(tasks: &[Task], task: &Task) -> Vec<usize> {
+    task.depends_on
+        .iter()
+        .copied()
+        .filter(|&id| {
+            tasks
+                .iter()
+                .any(|other| other.id == id && !other.state.is_finished())
+        })
+        .collect()
+}
+
+/// Whether `task` waits for a dependency before it can be started
+pub fn is_blocked(tasks: &[Task], task: &Task) -> bool {
+    task.state.is_initial() && !blockers(tasks, task).is_empty()
+}
+
+/// Checks that the task with the given id may depend on `depends_on`: all
+/// of them have to exist and none may depend on the task, directly or not
+pub fn validate(tasks: &[Task], id: usize, depends_on: &[usize]) -> Result<(), Error> {
+    for &dependency in depends_on {
+        if !tasks.iter().any(|task| task.id == dependency) {
+            return Err(Error::TaskNotFound(dependency));
+        }
+        if dependency == id || reaches(tasks, dependency, id) {
+            return Err(Error::DependencyCycle(id, dependency));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the task with the id `from` depends on `to` through any chain of
+/// dependencies
+fn reaches(tasks: &[Task], from: usize, to: usize) -> bool {
+    let mut found = vec![from];
+    let mut next = 0;
+    while next < found.len() {
+        let current = found[next];
+        for task in tasks.iter().filter(|task| task.id == current) {
+            for &dependency in &task.depends_on {
+                if dependency == to {
+                    return true;
+                }
+                if !found.contains(&dependency) {
+                    found.push(dependency);
+                }
+            }
+        }
+        next += 1;
+    }
+    false
+}
+
+/// Drops the task with the given id from the dependencies of all others,
+/// before it gets deleted, and returns the tasks that changed
+pub fn remove_dependency(tasks: &mut [Task], id: usize) -> Vec<Task> {
+    let mut changed = Vec::new();
+    for task in tasks
+        .iter_mut()
+        .filter(|task| task.depends_on.contains(&id))
+    {
+        task.depends_on.retain(|&dependency| dependency != id);
+        changed.push(task.clone());
+    }
+    changed
+}
+
+/// Reads task ids separated by whitespace or commas, each optionally written
+/// as `#3`. Returns `None` if any of them is not a number.
+pub fn parse(input: &str) -> Option<Vec<usize>> {
+    let mut ids = Vec::new();
+    for word in input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+    {
+        let id = word.trim_start_matches('#').parse().ok()?;
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    Some(ids)
+}
+
+pub fn format(ids: &[usize]) -> String {
+    ids.iter()
+        .map(|id| format!("#{}", id))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tasks 1, 2 and 3, where 1 depends on 2 and 2 on 3
+    fn chain() -> Vec<Task> {
+        (1..=3)
+            .map(|id| {
+                let mut task = Task::create_task(id, format!("task {}", id));
+                task.depends_on = if id < 3 { vec![id + 1] } else { Vec::new() };
+                task
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_task_cannot_depend_on_itself() {
+        assert!(matches!(
+            validate(&chain(), 3, &[3]),
+            Err(Error::DependencyCycle(3, 3))
+        ));
+    }
+
+    #[test]
+    fn indirect_cycles_are_refused() {
+        let tasks = chain();
+        assert!(matches!(
+            validate(&tasks, 3, &[1]),
+            Err(Error::DependencyCycle(3, 1))
+        ));
+        assert!(matches!(
+            validate(&tasks, 3, &[2]),
+            Err(Error::DependencyCycle(3, 2))
+        ));
+        assert!(validate(&tasks, 1, &[2, 3]).is_ok());
+    }
+
+    #[test]
+    fn unknown_dependencies_are_not_found() {
+        assert!(matches!(
+            validate(&chain(), 1, &[2, 9]),
+            Err(Error::TaskNotFound(9))
+        ));
+    }
+
+    #[test]
+    fn ids_parse_with_or_without_hash() {
+        assert_eq!(parse("#1, 2 #3 2"), Some(vec![1, 2, 3]));
+        assert_eq!(parse(""), Some(Vec::new()));
+        assert_eq!(parse("1 two"), None);
+    }
+}
@@ -0,0 +1,92 @@
+//! Every state change of a task is kept in its `history`, oldest first. The
+//! `started_at` and `finished_at` timestamps are derived from it: a task is
+//! started when it first enters a started state after last being in the
+//! initial one, and finished when its latest state change took it to a
+//! finished state.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Task, TaskState};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Transition {
+    pub from: TaskState,
+    pub to: TaskState,
+    pub at: DateTime<Utc>,
+}
+
+pub fn started_at(history: &[Transition]) -> Option<DateTime<Utc>> {
+    let mut started_at = None;
+    for transition in history {
+        if transition.to.is_initial() {
+            started_at = None;
+        } else if transition.to.is_started() {
+            started_at.get_or_insert(transition.at);
+        }
+    }
+    started_at
+}
+
+pub fn finished_at(history: &[Transition]) -> Option<DateTime<Utc>> {
+    history
+        .last()
+        .filter(|transition| transition.to.is_finished())
+        .map(|transition| transition.at)
+}
+
+/// Makes up a history from the timestamps of a task from before histories
+/// were kept, so the timestamps derived from it stay the same
+pub fn backfill(task: &mut Task) {
+    if !task.history.is_empty() {
+        return;
+    }
+
+    let initial = TaskState::new();
+    let mut state = initial.clone();
+    if let Some(at) = task.started_at {
+        let started = if task.state.is_started() {
+            task.state.clone()
+        } else {
+            (0..TaskState::count())
+                .map(TaskState::at)
+                .find(TaskState::is_started)
+                .unwrap_or_else(|| task.state.clone())
+        };
+        task.history.push(Transition {
+            from: initial,
+            to: started.clone(),
+            at,
+        });
+        state = started;
+    }
+    if let Some(at) = task.finished_at.filter(|_| task.state != state) {
+        task.history.push(Transition {
+            from: state,
+            to: task.state.clone(),
+            at,
+        });
+    }
+}
+
+/// Time spent in the state a transition left, starting from when the
+/// previous one happened or the task was created
+pub fn time_in_state(task: &Task, index: usize) -> TimeDelta {
+    let since = match index {
+        0 => task.created_at,
+        _ => task.history[index - 1].at,
+    };
+    task.history[index].at - since
+}
+
+/// Rounds `duration` to its two largest units, like `3d 4h` or `25m`
+pub fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => String::from("<1m"),
+        (0, 0, minutes) => format!("{}m", minutes),
+        (0, hours, minutes) => format!("{}h {}m", hours, minutes),
+        (days, hours, _) => format!("{}d {}h", days, hours),
+    }
+}
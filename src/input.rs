@@ -0,0 +1,158 @@
+use unicode_width::UnicodeWidthStr;
+
+/// Text buffer with a cursor, used by the input popup and the notes editor.
+/// Only the notes editor inserts line breaks.
+#[derive(Default)]
+pub struct Input {
+    value: String,
+    /// Cursor position counted in chars, not bytes
+    cursor: usize,
+}
+
+impl Input {
+    pub fn with_value(value: String) -> Input {
+        let cursor = value.chars().count();
+        Input { value, cursor }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Takes the current value out of the buffer, leaving it empty
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.value)
+    }
+
+    /// Display width of the text in front of the cursor on its line
+    pub fn cursor_width(&self) -> usize {
+        let start = self.line_start(self.cursor);
+        self.value[self.byte_index(start)..self.byte_index(self.cursor)].width()
+    }
+
+    /// Line the cursor is on, counted from 0
+    pub fn cursor_line(&self) -> usize {
+        self.value
+            .chars()
+            .take(self.cursor)
+            .filter(|&c| c == '\n')
+            .count()
+    }
+
+    /// Char index of the start of the line the char index `at` is on
+    fn line_start(&self, at: usize) -> usize {
+        let chars: Vec<char> = self.value.chars().collect();
+        let mut start = at;
+        while start > 0 && chars[start - 1] != '\n' {
+            start -= 1;
+        }
+        start
+    }
+
+    /// Char index of the end of the line the char index `at` is on
+    fn line_end(&self, at: usize) -> usize {
+        let chars: Vec<char> = self.value.chars().collect();
+        let mut end = at;
+        while end < chars.len() && chars[end] != '\n' {
+            end += 1;
+        }
+        end
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(char_index)
+            .map_or(self.value.len(), |(index, _)| index)
+    }
+
+    fn len(&self) -> usize {
+        self.value.chars().count()
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let index = self.byte_index(self.cursor);
+        self.value.insert(index, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let index = self.byte_index(self.cursor);
+            self.value.remove(index);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.len() {
+            let index = self.byte_index(self.cursor);
+            self.value.remove(index);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.len());
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = self.line_start(self.cursor);
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.line_end(self.cursor);
+    }
+
+    /// Moves the cursor to the same column on the previous line, or as
+    /// close to it as that line allows
+    pub fn move_up(&mut self) {
+        let start = self.line_start(self.cursor);
+        if start > 0 {
+            let column = self.cursor - start;
+            let previous_start = self.line_start(start - 1);
+            self.cursor = (previous_start + column).min(start - 1);
+        }
+    }
+
+    /// Moves the cursor to the same column on the next line, or as close to
+    /// it as that line allows
+    pub fn move_down(&mut self) {
+        let end = self.line_end(self.cursor);
+        if end < self.len() {
+            let column = self.cursor - self.line_start(self.cursor);
+            let next_start = end + 1;
+            self.cursor = (next_start + column).min(self.line_end(next_start));
+        }
+    }
+
+    /// Moves the cursor to the start of the previous word
+    pub fn move_word_left(&mut self) {
+        let chars: Vec<char> = self.value.chars().collect();
+        let mut cursor = self.cursor;
+        while cursor > 0 && chars[cursor - 1].is_whitespace() {
+            cursor -= 1;
+        }
+        while cursor > 0 && !chars[cursor - 1].is_whitespace() {
+            cursor -= 1;
+        }
+        self.cursor = cursor;
+    }
+
+    /// Moves the cursor past the end of the next word
+    pub fn move_word_right(&mut self) {
+        let chars: Vec<char> = self.value.chars().collect();
+        let mut cursor = self.cursor;
+        while cursor < chars.len() && chars[cursor].is_whitespace() {
+            cursor += 1;
+        }
+        while cursor < chars.len() && !chars[cursor].is_whitespace() {
+            cursor += 1;
+        }
+        self.cursor = cursor;
+    }
+}
@@ -1,20 +1,19 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use crossterm::{
     event,
-    event::KeyCode,
+    event::{KeyCode, KeyEvent, KeyModifiers},
     terminal::{disable_raw_mode, enable_raw_mode},
 };
 
 use serde::{Deserialize, Serialize};
 use std::{
+    cmp::Reverse,
+    collections::HashSet,
     convert::TryFrom,
-    fs::{File, OpenOptions},
-    io::{self, Read, Seek, SeekFrom},
-    path::PathBuf,
-    sync::mpsc,
+    fmt, io, process,
+    sync::{mpsc, Arc, Condvar, Mutex, MutexGuard},
     thread,
     time::{Duration, Instant},
-    usize,
 };
 use thiserror::Error;
 use tui::{
@@ -24,96 +23,728 @@ use tui::{
     text::{Span, Spans},
     widgets::{
         Block, BorderType, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table,
-        Tabs,
+        Tabs, Wrap,
     },
     Terminal,
 };
-use unicode_width::UnicodeWidthStr;
 
-const DB_PATH: &str = "./data/db.json";
-
-fn find_default_db_file() -> Option<PathBuf> {
-    home::home_dir().map(|mut path| {
-        path.push(DB_PATH);
-        path
-    })
-}
-
-fn ensure_db_file_exists(path: PathBuf) -> Result<File, Error> {
-    let file = {
-        if path.exists() && path.is_file() {
-            OpenOptions::new().read(true).write(true).open(path)?
-        } else {
-            match path.parent() {
-                Some(parent) => std::fs::create_dir_all(parent)?,
-                None => (),
-            };
-
-            OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(path)?
-        }
-    };
-
-    Ok(file)
-}
+mod archive;
+mod cli;
+mod commands;
+mod config;
+mod dates;
+mod dependencies;
+mod history;
+mod input;
+mod notes;
+mod output;
+mod projects;
+mod store;
+mod subtasks;
+mod tags;
+mod trash;
+mod undo;
+mod watch;
+mod workflow;
+
+use clap::Parser;
+use cli::{Cli, Command};
+use config::{Config, SortOrder};
+use history::Transition;
+use input::Input;
+use projects::{ProjectFilter, ProjectRow};
+use store::{Change, Location, TaskStore, DEFAULT_LIST};
+use subtasks::TreeRow;
+use undo::UndoHistory;
+use watch::FileWatcher;
 
 #[derive(PartialEq)]
 enum InputMode {
     Normal,
     Editing,
+    /// Renaming the task with the given id
+    Renaming(usize),
+    /// Asking whether to reload a database that another process changed
+    ReloadPrompt,
+    /// Entering the name of a new task list
+    NamingList,
+    /// Setting the due date of the task with the given id
+    SettingDue(usize),
+    /// Editing the tags of the task with the given id
+    Tagging(usize),
+    /// Entering the tags the task list is filtered by
+    Filtering,
+    /// Moving the task with the given id to another project
+    SettingProject(usize),
+    /// Adding a subtask to the task with the given id
+    AddingSubtask(usize),
+    /// Editing the dependencies of the task with the given id
+    SettingDependencies(usize),
+    /// Editing the notes of the task with the given id
+    EditingNotes(usize),
+    /// Picking any state for the task with the given id
+    PickingState(usize),
+    /// Asking whether to move the task with the given id to the trash
+    ConfirmingDelete(usize),
+    /// Asking whether to delete the task with the given id from the trash
+    ConfirmingPurge(usize),
+    /// Entering what to search the archive for
+    SearchingArchive,
+}
+
+/// Message shown in the footer until the next key press
+enum Status {
+    Info(String),
+    /// A change that was refused or failed
+    Error(String),
 }
 
 /// App holds the state of the application
 struct App {
     /// Current value of the input box
-    input: String,
+    input: Input,
     /// Current input mode
     input_mode: InputMode,
+    config: Config,
+    /// Where the task lists are kept on disk
+    location: Location,
+    /// Names of all task lists
+    lists: Vec<String>,
+    /// Name of the task list that is currently open
+    list_name: String,
+    /// Where the tasks of the current list are persisted
+    store: Box<dyn TaskStore>,
+    /// Tasks as shown, including changes that are not written yet
+    tasks: Vec<Task>,
+    /// Tasks in the trash, most recently deleted first
+    trash: Vec<Task>,
+    /// Tasks in the archive of the list, most recently finished first
+    archived: Vec<Task>,
+    /// Only archived tasks matching this are shown, see [`archive::matches`]
+    archive_query: String,
+    /// Id the next added task gets, the one the store hands out for it
+    next_id: usize,
+    /// `tasks` and `trash` as they were after the latest change, to tell what the next
+    /// one changed
+    snapshot: Vec<Task>,
+    /// Changes made in this session that can be undone or redone
+    undo_history: UndoHistory,
+    /// Changes waiting to be written to the store, oldest first
+    pending: Vec<Change>,
+    /// When the most recent change was made
+    last_change: Instant,
+    /// How long to wait after the most recent change before writing
+    save_delay: Duration,
+    /// Order `tasks` are kept in
+    sort: SortOrder,
+    /// Only tasks with at least one of these tags are shown, unless it is empty
+    tag_filter: Vec<String>,
+    /// Project selected in the sidebar
+    project_filter: ProjectFilter,
+    /// Tasks whose subtasks are hidden
+    collapsed: HashSet<usize>,
+    status: Option<Status>,
 }
 
-impl Default for App {
-    fn default() -> App {
-        App {
-            input: String::new(),
+impl App {
+    fn new(config: Config, location: Location, list_name: String) -> Result<App, Error> {
+        let store = store::open_store(&config, location.path(&list_name))?;
+        let save_delay = Duration::from_millis(config.save_delay_ms);
+        let sort = config.sort;
+
+        let mut app = App {
+            input: Input::default(),
             input_mode: InputMode::Normal,
+            config,
+            location,
+            lists: Vec::new(),
+            list_name,
+            store,
+            tasks: Vec::new(),
+            trash: Vec::new(),
+            archived: Vec::new(),
+            archive_query: String::new(),
+            next_id: 0,
+            snapshot: Vec::new(),
+            undo_history: UndoHistory::default(),
+            pending: Vec::new(),
+            last_change: Instant::now(),
+            save_delay,
+            sort,
+            tag_filter: Vec::new(),
+            project_filter: ProjectFilter::All,
+            collapsed: HashSet::new(),
+            status: None,
+        };
+        app.reload()?;
+        app.purge_expired()?;
+        app.archive_expired()?;
+        app.refresh_lists()?;
+        Ok(app)
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        let tasks = self.store.list()?;
+        self.set_tasks(tasks);
+        self.archived = self.store.archived()?;
+        self.sort_archive();
+        self.next_id = self.store.next_id()?;
+        self.snapshot = self.all_tasks();
+        Ok(())
+    }
+
+    fn sort_archive(&mut self) {
+        self.archived
+            .sort_by_key(|task| (Reverse(task.finished_at), Reverse(task.id)));
+    }
+
+    /// Archived tasks shown in the archive, in the order they are shown
+    fn visible_archive(&self) -> Vec<&Task> {
+        self.archived
+            .iter()
+            .filter(|task| archive::matches(task, &self.archive_query))
+            .collect()
+    }
+
+    /// Splits `tasks` into the ones shown and the ones in the trash
+    fn set_tasks(&mut self, tasks: Vec<Task>) {
+        let (mut trash, tasks): (Vec<Task>, Vec<Task>) =
+            tasks.into_iter().partition(Task::is_deleted);
+        trash.sort_by_key(|task| Reverse(task.deleted_at));
+        self.tasks = tasks;
+        self.trash = trash;
+        self.sort_tasks();
+    }
+
+    /// Tasks shown and in the trash, together
+    fn all_tasks(&self) -> Vec<Task> {
+        self.tasks.iter().chain(&self.trash).cloned().collect()
+    }
+
+    fn sort_tasks(&mut self) {
+        match self.sort {
+            SortOrder::Id => self.tasks.sort_by_key(|task| task.id),
+            SortOrder::Priority => self.tasks.sort_by_key(|task| {
+                (
+                    Reverse(task.priority),
+                    task.due_at.is_none(),
+                    task.due_at,
+                    task.id,
+                )
+            }),
         }
     }
-}
 
-#[derive(Serialize, Deserialize, Clone)]
-enum TaskState {
-    Pending,
-    Started,
-    InProgress,
-    Done,
+    fn toggle_sort(&mut self) {
+        self.sort = match self.sort {
+            SortOrder::Id => SortOrder::Priority,
+            SortOrder::Priority => SortOrder::Id,
+        };
+        self.sort_tasks();
+    }
+
+    /// Tasks shown in the task list, in the order they are shown
+    fn visible_tasks(&self) -> Vec<TreeRow<'_>> {
+        let matching: Vec<&Task> = self
+            .tasks
+            .iter()
+            .filter(|task| task.has_any_tag(&self.tag_filter) && self.project_filter.matches(task))
+            .collect();
+        subtasks::flatten(&self.tasks, &matching, &self.collapsed)
+    }
+
+    /// Hides the subtasks of the visible task at `index`. If there are none
+    /// or they are hidden already, returns the index of its parent instead.
+    fn collapse_or_find_parent(&mut self, index: usize) -> Option<usize> {
+        let rows = self.visible_tasks();
+        let row = rows.get(index)?;
+        let id = row.task.id;
+        if row.has_children && !self.collapsed.contains(&id) {
+            self.collapsed.insert(id);
+            None
+        } else {
+            let parent_id = row.task.parent_id?;
+            rows.iter().position(|row| row.task.id == parent_id)
+        }
+    }
+
+    /// Selects the next or previous project in the sidebar, wrapping around
+    fn move_project_selection(&mut self, forward: bool) {
+        let rows = projects::tree(&self.tasks);
+        let current = rows
+            .iter()
+            .position(|row| row.filter == self.project_filter)
+            .unwrap_or(0);
+        let next = if forward {
+            (current + 1) % rows.len()
+        } else {
+            (current + rows.len() - 1) % rows.len()
+        };
+        self.project_filter = rows[next].filter.clone();
+    }
+
+    fn refresh_lists(&mut self) -> Result<(), Error> {
+        self.lists = self.location.lists()?;
+        if !self.lists.contains(&self.list_name) {
+            self.lists.push(self.list_name.clone());
+        }
+        Ok(())
+    }
+
+    /// Writes what is pending for the current list and switches to the list
+    /// called `name`, creating it if needed
+    fn open_list(&mut self, name: String) -> Result<(), Error> {
+        self.flush()?;
+        self.store = store::open_store(&self.config, self.location.path(&name))?;
+        self.list_name = name;
+        self.undo_history.clear();
+        self.archive_query.clear();
+        self.reload()?;
+        self.purge_expired()?;
+        self.archive_expired()?;
+        self.refresh_lists()
+    }
+
+    /// Queues a change that was already applied to `tasks`
+    fn record(&mut self, change: Change) -> Result<(), Error> {
+        self.record_all(vec![change])
+    }
+
+    /// Queues changes that were already applied to `tasks`, to be written
+    /// together and undone as one step
+    fn record_all(&mut self, changes: Vec<Change>) -> Result<(), Error> {
+        self.undo_history.record(&self.snapshot, &changes);
+        self.queue(changes)
+    }
+
+    /// Queues changes that were already applied to `tasks` without making
+    /// them a step that can be undone
+    fn queue(&mut self, changes: Vec<Change>) -> Result<(), Error> {
+        self.sort_tasks();
+        self.snapshot = self.all_tasks();
+        self.pending.extend(changes);
+        self.last_change = Instant::now();
+        if self.save_delay.is_zero() {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes the pending changes once the save delay has passed
+    fn flush_if_due(&mut self) -> Result<(), Error> {
+        if self.last_change.elapsed() >= self.save_delay {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes all pending changes at once and reloads the tasks from the
+    /// store. Changes to tasks that no longer exist are dropped.
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        while !self.pending.is_empty() {
+            match self.store.apply_all(self.pending.clone()) {
+                Ok(()) => self.pending.clear(),
+                Err(Error::TaskNotFound(id)) => {
+                    self.pending.retain(|change| change.task_id() != id)
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        self.reload()
+    }
+
+    /// Adds a task, as a subtask of the task with the id `parent_id` if given.
+    /// Subtasks go into the project of their parent, other tasks into the
+    /// project selected in the sidebar.
+    fn add_task(&mut self, name: String, parent_id: Option<usize>) -> Result<(), Error> {
+        let mut task = Task::create_task(self.next_id, name);
+        self.next_id += 1;
+        task.project = match parent_id.and_then(|id| self.tasks.iter().find(|task| task.id == id)) {
+            Some(parent) => parent.project.clone(),
+            None => self.project_filter.project(),
+        };
+        if let Some(parent_id) = parent_id {
+            task.parent_id = Some(parent_id);
+            self.collapsed.remove(&parent_id);
+        }
+        self.tasks.push(task.clone());
+        self.record(Change::Insert(task))
+    }
+
+    /// Progresses the task with the given id
+    fn progress_task(&mut self, id: usize) -> Result<(), Error> {
+        match self.tasks.iter().find(|task| task.id == id) {
+            Some(task) => {
+                let next = task.state.progress();
+                self.set_task_state(id, next)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Moves the task with the given id back to its previous state
+    fn regress_task(&mut self, id: usize) -> Result<(), Error> {
+        match self.tasks.iter().find(|task| task.id == id) {
+            Some(task) => {
+                let previous = task.state.regress();
+                self.set_task_state(id, previous)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Moves the task with the given id to `state`, completing its subtasks
+    /// once it is finished, and reports the transition in the footer. Nothing
+    /// changes if the task or a subtask is blocked.
+    fn set_task_state(&mut self, id: usize, state: TaskState) -> Result<(), Error> {
+        let mut task = match self.tasks.iter().find(|task| task.id == id) {
+            Some(task) => task.clone(),
+            None => return Ok(()),
+        };
+        let from = task.state.clone();
+        if from == state {
+            return Ok(());
+        }
+        task.set_state(state, &self.tasks)?;
+        let completed = if task.state.is_finished() && !from.is_finished() {
+            subtasks::complete_descendants(&mut self.tasks, id)?
+        } else {
+            Vec::new()
+        };
+
+        if let Some(element) = self.tasks.iter_mut().find(|element| element.id == id) {
+            *element = task.clone();
+        }
+        self.status = Some(Status::Info(format!(
+            "task {}: {} -> {}",
+            id, from, task.state
+        )));
+        let mut changes = vec![Change::Update(task)];
+        changes.extend(completed.into_iter().map(Change::Update));
+        self.record_all(changes)
+    }
+
+    /// Makes the task with the given id depend on `depends_on`, unless that
+    /// would create a cycle
+    fn set_dependencies(&mut self, id: usize, depends_on: Vec<usize>) -> Result<(), Error> {
+        dependencies::validate(&self.tasks, id, &depends_on)?;
+        self.update_task(id, |task| task.depends_on = depends_on)
+    }
+
+    /// Applies `edit` to the task with the given id and queues the result
+    fn update_task(&mut self, id: usize, edit: impl FnOnce(&mut Task)) -> Result<(), Error> {
+        if let Some(element) = self.tasks.iter_mut().find(|task| task.id == id) {
+            edit(element);
+            let change = Change::Update(element.clone());
+            self.record(change)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the task with the given id to the trash
+    fn trash_task(&mut self, id: usize) -> Result<(), Error> {
+        if let Some(index) = self.tasks.iter().position(|task| task.id == id) {
+            let changed = trash::trash(&mut self.tasks, id);
+            let task = self.tasks.remove(index);
+            self.trash.insert(0, task);
+            self.status = Some(Status::Info(format!("Moved task {} to the trash", id)));
+            self.record_all(changed.into_iter().map(Change::Update).collect())?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes the task with the given id out of the trash
+    fn restore_task(&mut self, id: usize) -> Result<(), Error> {
+        if let Some(index) = self.trash.iter().position(|task| task.id == id) {
+            let mut task = self.trash.remove(index);
+            trash::restore(&self.tasks, &mut task);
+            self.tasks.push(task.clone());
+            self.status = Some(Status::Info(format!("Restored task {}", id)));
+            self.record(Change::Update(task))?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the task with the given id from the trash for good
+    fn purge_task(&mut self, id: usize) -> Result<(), Error> {
+        if let Some(index) = self.trash.iter().position(|task| task.id == id) {
+            self.trash.remove(index);
+            self.status = Some(Status::Info(format!("Purged task {}", id)));
+            self.record(Change::Delete(id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Purges the tasks that were in the trash for longer than configured.
+    /// This cannot be undone.
+    fn purge_expired(&mut self) -> Result<(), Error> {
+        let expired = trash::expired(&self.trash, self.config.trash_days, Utc::now());
+        if expired.is_empty() {
+            return Ok(());
+        }
+        self.trash.retain(|task| !expired.contains(&task.id));
+        self.queue(expired.into_iter().map(Change::Delete).collect())
+    }
+
+    /// Moves the task with the given id to the archive, if it is finished
+    fn archive_task(&mut self, id: usize) -> Result<(), Error> {
+        if let Some(index) = self.tasks.iter().position(|task| task.id == id) {
+            archive::ensure_archivable(&self.tasks[index])?;
+            let task = self.tasks.remove(index);
+            self.archived.push(task);
+            self.sort_archive();
+            self.status = Some(Status::Info(format!("Archived task {}", id)));
+            self.record(Change::Archive(id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the task with the given id out of the archive, back into the list
+    fn unarchive_task(&mut self, id: usize) -> Result<(), Error> {
+        if let Some(index) = self.archived.iter().position(|task| task.id == id) {
+            let task = self.archived.remove(index);
+            self.tasks.push(task);
+            self.status = Some(Status::Info(format!("Unarchived task {}", id)));
+            self.record(Change::Unarchive(id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Archives the tasks that were finished for longer than configured.
+    /// This is not a step that can be undone.
+    fn archive_expired(&mut self) -> Result<(), Error> {
+        let expired = archive::expired(&self.tasks, self.config.archive_days, Utc::now());
+        if expired.is_empty() {
+            return Ok(());
+        }
+        let (archived, tasks): (Vec<Task>, Vec<Task>) = self
+            .tasks
+            .drain(..)
+            .partition(|task| expired.contains(&task.id));
+        self.tasks = tasks;
+        self.archived.extend(archived);
+        self.sort_archive();
+        self.queue(expired.into_iter().map(Change::Archive).collect())
+    }
+
+    /// Reverts the latest change made in the app
+    fn undo(&mut self) -> Result<(), Error> {
+        match self.undo_history.undo() {
+            Some((description, changes)) => {
+                self.status = Some(Status::Info(format!("Undid {}", description)));
+                self.apply_changes(changes)
+            }
+            None => {
+                self.status = Some(Status::Info(String::from("Nothing to undo")));
+                Ok(())
+            }
+        }
+    }
+
+    /// Makes the latest undone change again
+    fn redo(&mut self) -> Result<(), Error> {
+        match self.undo_history.redo() {
+            Some((description, changes)) => {
+                self.status = Some(Status::Info(format!("Redid {}", description)));
+                self.apply_changes(changes)
+            }
+            None => {
+                self.status = Some(Status::Info(String::from("Nothing to redo")));
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies changes to `tasks` and queues them. Changes that no longer
+    /// fit, because another program changed the tasks meanwhile, are dropped.
+    fn apply_changes(&mut self, changes: Vec<Change>) -> Result<(), Error> {
+        let mut tasks = self.all_tasks();
+        let mut applied = Vec::new();
+        for change in changes {
+            if change
+                .clone()
+                .apply_to(&mut tasks, &mut self.archived)
+                .is_ok()
+            {
+                applied.push(change);
+            }
+        }
+        self.set_tasks(tasks);
+        self.sort_archive();
+        self.queue(applied)
+    }
+
+    /// Turns a conflicting write into a reload prompt and a refused change
+    /// into a message in the footer, passing on any other error
+    fn handle_error(&mut self, error: Error) -> Result<(), Error> {
+        match error {
+            Error::DatabaseChanged => {
+                self.input.take();
+                self.input_mode = InputMode::ReloadPrompt;
+                Ok(())
+            }
+            Error::Blocked { .. }
+            | Error::DependencyCycle(..)
+            | Error::TransitionNotAllowed { .. }
+            | Error::NotFinished(_)
+            | Error::TaskNotFound(_)
+            | Error::EditorError(_) => {
+                self.status = Some(Status::Error(error.to_string()));
+                Ok(())
+            }
+            error => Err(error),
+        }
+    }
+
+    /// Answers the reload prompt: either drop the pending changes and show
+    /// what is on disk, or apply the pending changes on top of it
+    fn resolve_conflict(&mut self, reload: bool) -> Result<(), Error> {
+        self.input_mode = InputMode::Normal;
+        if reload {
+            self.pending.clear();
+            self.undo_history.clear();
+            self.reload()
+        } else {
+            self.store.list()?;
+            self.renumber_added_tasks()?;
+            self.flush()
+        }
+    }
+
+    /// Gives tasks added here new ids if another process handed out theirs
+    /// meanwhile. The undo history refers to the old ids, so it is cleared.
+    fn renumber_added_tasks(&mut self) -> Result<(), Error> {
+        let store_next_id = self.store.next_id()?;
+        let taken: Vec<usize> = self
+            .pending
+            .iter()
+            .filter_map(|change| match change {
+                Change::Insert(task) if task.id < store_next_id => Some(task.id),
+                _ => None,
+            })
+            .collect();
+        if taken.is_empty() {
+            return Ok(());
+        }
+
+        let mut next_id = self.next_id.max(store_next_id);
+        let mut renumbered = Vec::new();
+        for id in taken {
+            for change in &mut self.pending {
+                change.renumber(id, next_id);
+            }
+            renumbered.push(format!("{} -> {}", id, next_id));
+            next_id += 1;
+        }
+        self.next_id = next_id;
+        self.undo_history.clear();
+        self.status = Some(Status::Info(format!(
+            "Added tasks got new ids: {}",
+            renumbered.join(", ")
+        )));
+        Ok(())
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent) {
+        let word_jump = key.modifiers.contains(KeyModifiers::CONTROL)
+            || key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char(c) => self.input.insert(c),
+            KeyCode::Backspace => self.input.backspace(),
+            KeyCode::Delete => self.input.delete(),
+            KeyCode::Left if word_jump => self.input.move_word_left(),
+            KeyCode::Right if word_jump => self.input.move_word_right(),
+            KeyCode::Left => self.input.move_left(),
+            KeyCode::Right => self.input.move_right(),
+            KeyCode::Home => self.input.move_home(),
+            KeyCode::End => self.input.move_end(),
+            _ => {}
+        }
+    }
 }
 
+/// A state of the configured workflow, see [`workflow`]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+struct TaskState(String);
+
 impl TaskState {
     fn new() -> TaskState {
-        TaskState::Pending
+        TaskState::at(0)
     }
 
-    fn to_string(&self) -> String {
-        {
-            match self {
-                TaskState::Pending => "pending",
-                TaskState::Started => "started",
-                TaskState::InProgress => "in progress",
-                TaskState::Done => "done",
-            }
+    /// State at the given position in the workflow
+    fn at(index: usize) -> TaskState {
+        TaskState(workflow::current().state(index).name.clone())
+    }
+
+    /// Number of states, the bound of `TryFrom<usize>`
+    fn count() -> usize {
+        workflow::current().count()
+    }
+
+    /// First finished state, where marking a task as done takes it
+    fn done() -> TaskState {
+        let workflow = workflow::current();
+        let index = (0..workflow.count())
+            .find(|&index| workflow.state(index).finished)
+            .expect("the workflow has a finished state");
+        TaskState::at(index)
+    }
+
+    /// Position in the workflow, the inverse of `TryFrom<usize>`
+    fn index(&self) -> usize {
+        workflow::current().find_stored(&self.0).unwrap_or_default()
+    }
+
+    /// Whether this is the first state, the one tasks start out in
+    fn is_initial(&self) -> bool {
+        self.index() == 0
+    }
+
+    fn is_started(&self) -> bool {
+        workflow::current().state(self.index()).started
+    }
+
+    fn is_finished(&self) -> bool {
+        workflow::current().state(self.index()).finished
+    }
+
+    /// Whether the workflow lets a task move from this state to `state`
+    fn allows(&self, state: &TaskState) -> bool {
+        workflow::current().allows(self.index(), state.index())
+    }
+
+    fn color(&self) -> Option<Color> {
+        workflow::current().color(self.index())
+    }
+
+    /// The next state in the workflow, or this one if there is none
+    fn progress(&self) -> Self {
+        match workflow::current().next(self.index()) {
+            Some(next) => TaskState::at(next),
+            None => self.clone(),
         }
-        .to_string()
     }
 
-    fn progress(&mut self) -> Self {
-        match self {
-            TaskState::Pending => TaskState::Started,
-            TaskState::Started => TaskState::InProgress,
-            TaskState::InProgress => TaskState::Done,
-            TaskState::Done => TaskState::Done,
+    /// The state before this one in the workflow, the inverse of `progress`
+    fn regress(&self) -> Self {
+        match workflow::current().previous(self.index()) {
+            Some(previous) => TaskState::at(previous),
+            None => self.clone(),
         }
     }
 }
@@ -124,21 +755,38 @@ impl Default for TaskState {
     }
 }
 
+impl fmt::Display for TaskState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<TaskState> for String {
+    fn from(state: TaskState) -> String {
+        state.0
+    }
+}
+
+/// Looks the state up by its name or one of its aliases, or by the role of
+/// a built-in state the workflow doesn't define
 impl TryFrom<&str> for TaskState {
     type Error = Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(match value {
-            "pending" => TaskState::Pending,
-            "started" => TaskState::Started,
-            "in progress" => TaskState::InProgress,
-            "done" => TaskState::Done,
-            _ => {
-                return Err(Self::Error::StringError(String::from(
-                    "input was not a valid TaskState",
-                )))
-            }
-        })
+        workflow::current()
+            .find_stored(value)
+            .map(TaskState::at)
+            .ok_or_else(|| {
+                Self::Error::StringError(format!("'{}' is not a state of the workflow", value))
+            })
+    }
+}
+
+impl TryFrom<String> for TaskState {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        TaskState::try_from(value.as_str())
     }
 }
 
@@ -146,14 +794,86 @@ impl TryFrom<usize> for TaskState {
     type Error = Error;
 
     fn try_from(value: usize) -> Result<Self, Self::Error> {
+        if value < TaskState::count() {
+            Ok(TaskState::at(value))
+        } else {
+            Err(Self::Error::StringError(String::from(
+                "input was not a valid TaskState",
+            )))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    fn raise(self) -> Self {
+        match self {
+            Priority::None => Priority::Low,
+            Priority::Low => Priority::Medium,
+            Priority::Medium => Priority::High,
+            Priority::High | Priority::Urgent => Priority::Urgent,
+        }
+    }
+
+    fn lower(self) -> Self {
+        match self {
+            Priority::None | Priority::Low => Priority::None,
+            Priority::Medium => Priority::Low,
+            Priority::High => Priority::Medium,
+            Priority::Urgent => Priority::High,
+        }
+    }
+
+    /// Short marker shown in front of the task name, empty for no priority
+    fn marker(self) -> Span<'static> {
+        let (text, color) = match self {
+            Priority::None => ("", Color::White),
+            Priority::Low => ("L", Color::Blue),
+            Priority::Medium => ("M", Color::Cyan),
+            Priority::High => ("H", Color::Magenta),
+            Priority::Urgent => ("U", Color::LightRed),
+        };
+        Span::styled(
+            text,
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        )
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Priority::None => "none",
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+            Priority::Urgent => "urgent",
+        })
+    }
+}
+
+impl TryFrom<&str> for Priority {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
         Ok(match value {
-            0 => TaskState::Pending,
-            1 => TaskState::Started,
-            2 => TaskState::InProgress,
-            3 => TaskState::Done,
+            "none" => Priority::None,
+            "low" => Priority::Low,
+            "medium" => Priority::Medium,
+            "high" => Priority::High,
+            "urgent" => Priority::Urgent,
             _ => {
                 return Err(Self::Error::StringError(String::from(
-                    "input was not a valid TaskState",
+                    "input was not a valid Priority",
                 )))
             }
         })
@@ -168,6 +888,21 @@ struct Task {
     created_at: DateTime<Utc>,
     started_at: Option<DateTime<Utc>>,
     finished_at: Option<DateTime<Utc>>,
+    due_at: Option<DateTime<Utc>>,
+    priority: Priority,
+    tags: Vec<String>,
+    project: Option<String>,
+    /// Task this one is a subtask of
+    parent_id: Option<usize>,
+    /// Tasks that have to be done before this one can be started
+    depends_on: Vec<usize>,
+    /// Free-form description, possibly spanning several lines
+    notes: String,
+    /// Every state change, oldest first. `started_at` and `finished_at` are
+    /// derived from it.
+    history: Vec<Transition>,
+    /// When the task was moved to the trash, see [`trash`]
+    deleted_at: Option<DateTime<Utc>>,
 }
 
 impl Task {
@@ -181,23 +916,145 @@ impl Task {
             created_at: Utc::now(),
             started_at: None,
             finished_at: None,
+            due_at: None,
+            priority: Priority::default(),
+            tags: Vec::new(),
+            project: None,
+            parent_id: None,
+            depends_on: Vec::new(),
+            notes: String::new(),
+            history: Vec::new(),
+            deleted_at: None,
         }
     }
 
-    fn progress(&mut self) {
-        self.state = self.state.progress();
-        match self.state {
-            TaskState::Started => self.started_at = Some(Utc::now()),
-            TaskState::Done => self.finished_at = Some(Utc::now()),
-            _ => {}
+    fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Moves the task on to its next state, unless it is in the initial
+    /// state and any of its dependencies in `tasks` is not finished yet
+    fn progress(&mut self, tasks: &[Task]) -> Result<(), Error> {
+        let next = self.state.progress();
+        self.set_state(next, tasks)
+    }
+
+    fn ensure_unblocked(&self, tasks: &[Task]) -> Result<(), Error> {
+        if !self.state.is_initial() {
+            return Ok(());
+        }
+        let blockers = dependencies::blockers(tasks, self);
+        if blockers.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Blocked {
+                id: self.id,
+                blockers,
+            })
+        }
+    }
+
+    /// Moves the task back to its previous state
+    fn regress(&mut self, tasks: &[Task]) -> Result<(), Error> {
+        let previous = self.state.regress();
+        self.set_state(previous, tasks)
+    }
+
+    /// Moves the task to `state` if the workflow allows it, unless it would
+    /// leave the initial state while any of its dependencies in `tasks` is
+    /// not finished yet
+    fn set_state(&mut self, state: TaskState, tasks: &[Task]) -> Result<(), Error> {
+        if state == self.state {
+            return Ok(());
+        }
+        if !self.state.allows(&state) {
+            return Err(Error::TransitionNotAllowed {
+                id: self.id,
+                from: self.state.to_string(),
+                to: state.to_string(),
+            });
+        }
+        if !state.is_initial() {
+            self.ensure_unblocked(tasks)?;
+        }
+        self.move_to(state);
+        Ok(())
+    }
+
+    /// Moves the task to `state`, recording the transition in its history
+    /// and deriving the timestamps from that
+    fn move_to(&mut self, state: TaskState) {
+        if state == self.state {
+            return;
+        }
+
+        history::backfill(self);
+        self.history.push(Transition {
+            from: self.state.clone(),
+            to: state.clone(),
+            at: Utc::now(),
+        });
+        self.state = state;
+        self.started_at = history::started_at(&self.history);
+        self.finished_at = history::finished_at(&self.history);
+    }
+
+    /// Walks through the remaining states up to a finished one, so the
+    /// start time gets recorded as well. Transitions and dependencies are
+    /// not checked.
+    fn complete(&mut self) {
+        for _ in 0..TaskState::count() {
+            if self.state.is_finished() {
+                return;
+            }
+            let next = self.state.progress();
+            if next == self.state {
+                break;
+            }
+            self.move_to(next);
+        }
+        // The next states lead around in a circle or nowhere
+        if !self.state.is_finished() {
+            self.move_to(TaskState::done());
+        }
+    }
+
+    fn rename(&mut self, task_name: String) {
+        self.name = task_name;
+    }
+
+    /// Whether the task has one of `tags`, which is always true for no tags
+    fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag))
+    }
+
+    /// Colour marking the task as overdue or due within `soon` of `now`.
+    /// Finished tasks are never marked.
+    fn due_color(&self, now: DateTime<Utc>, soon: TimeDelta) -> Option<Color> {
+        let due_at = self.due_at.filter(|_| !self.state.is_finished())?;
+        if due_at <= now {
+            Some(Color::Red)
+        } else if due_at - now <= soon {
+            Some(Color::Yellow)
+        } else {
+            None
         }
     }
 
     fn create_table_row<'a>(&self) -> Row<'a> {
+        let due = self.due_at.map_or_else(|| "-".to_string(), dates::format);
         let mut cell_vec = vec![
             Cell::from(Span::raw(self.id.to_string())),
             Cell::from(Span::raw(self.name.clone())),
-            Cell::from(Span::raw(self.state.to_string())),
+            Cell::from(Span::raw(self.project.clone().unwrap_or_default())),
+            Cell::from(tag_chips(&self.tags)),
+            Cell::from(Span::styled(
+                self.state.to_string(),
+                self.state
+                    .color()
+                    .map_or_else(Style::default, |color| Style::default().fg(color)),
+            )),
+            Cell::from(Span::raw(due)),
             Cell::from(Span::raw(self.created_at.to_string())),
         ];
 
@@ -222,24 +1079,36 @@ impl Task {
                 "Name",
                 Style::default().add_modifier(Modifier::BOLD),
             )),
+            Cell::from(Span::styled(
+                "Project",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Tags",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
             Cell::from(Span::styled(
                 "State",
                 Style::default().add_modifier(Modifier::BOLD),
             )),
+            Cell::from(Span::styled(
+                "Due",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
             Cell::from(Span::styled(
                 "Created At",
                 Style::default().add_modifier(Modifier::BOLD),
             )),
         ];
 
-        if let Some(_) = self.started_at {
+        if self.started_at.is_some() {
             cell_vec.push(Cell::from(Span::styled(
                 "Started At",
                 Style::default().add_modifier(Modifier::BOLD),
             )));
         }
 
-        if let Some(_) = self.finished_at {
+        if self.finished_at.is_some() {
             cell_vec.push(Cell::from(Span::styled(
                 "Finished At",
                 Style::default().add_modifier(Modifier::BOLD),
@@ -249,37 +1118,22 @@ impl Task {
         Row::new(cell_vec)
     }
 
-    fn create_block_constraints<'a>(self) -> &'a [Constraint] {
-        match self.state {
-            TaskState::Pending => &[
-                Constraint::Percentage(5),
-                Constraint::Percentage(20),
-                Constraint::Percentage(15),
-                Constraint::Percentage(19),
-            ],
-            TaskState::Started => &[
-                Constraint::Percentage(5),
-                Constraint::Percentage(20),
-                Constraint::Percentage(15),
-                Constraint::Percentage(19),
-                Constraint::Percentage(19),
-            ],
-            TaskState::InProgress => &[
-                Constraint::Percentage(5),
-                Constraint::Percentage(20),
-                Constraint::Percentage(15),
-                Constraint::Percentage(19),
-                Constraint::Percentage(19),
-            ],
-            TaskState::Done => &[
-                Constraint::Percentage(5),
-                Constraint::Percentage(20),
-                Constraint::Percentage(15),
-                Constraint::Percentage(19),
-                Constraint::Percentage(19),
-                Constraint::Percentage(19),
-            ],
-        }
+    /// Column widths, including the start and finish times when they are shown
+    fn create_block_constraints<'a>(&self) -> &'a [Constraint] {
+        const WIDTHS: &[Constraint] = &[
+            Constraint::Percentage(4),
+            Constraint::Percentage(14),
+            Constraint::Percentage(10),
+            Constraint::Percentage(12),
+            Constraint::Percentage(8),
+            Constraint::Percentage(13),
+            Constraint::Percentage(13),
+            Constraint::Percentage(13),
+            Constraint::Percentage(13),
+        ];
+        let columns =
+            7 + usize::from(self.started_at.is_some()) + usize::from(self.finished_at.is_some());
+        &WIDTHS[..columns]
     }
 }
 
@@ -289,19 +1143,126 @@ pub enum Error {
     ReadDBError(#[from] io::Error),
     #[error("error parsing the DB file: {0}")]
     ParseDBError(#[from] serde_json::Error),
+    #[error("no task with id {0}")]
+    TaskNotFound(usize),
+    #[error("the DB file was changed by another process")]
+    DatabaseChanged,
+    #[error("the DB file has version {found}, but this task-tui only supports up to version {supported}; please upgrade")]
+    UnsupportedVersion { found: u64, supported: u64 },
+    #[error("error watching the DB file: {0}")]
+    WatchError(#[from] notify::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("error accessing the SQLite DB: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+    #[error("task {id} is blocked by {}", dependencies::format(.blockers))]
+    Blocked { id: usize, blockers: Vec<usize> },
+    #[error("task {0} cannot depend on task {1}, that would create a dependency cycle")]
+    DependencyCycle(usize, usize),
+    #[error("the workflow does not allow moving task {id} from {from} to {to}")]
+    TransitionNotAllowed { id: usize, from: String, to: String },
+    #[error("error running the editor: {0}")]
+    EditorError(io::Error),
+    #[error("task {0} is not finished, only finished tasks can be archived")]
+    NotFinished(usize),
+    #[error("error in the config file {path}: {message}")]
+    ConfigError { path: String, message: String },
     #[error("error: {0}")]
     StringError(String),
 }
 
+impl Error {
+    /// Exit status reported by the command line interface: 1 for general
+    /// errors, 3 for unknown tasks, 4 for write conflicts, 65 for unreadable
+    /// DB files, 74 for I/O failures and 78 for an invalid config file. Usage
+    /// errors exit with 2.
+    fn exit_code(&self) -> i32 {
+        match self {
+            Error::StringError(_)
+            | Error::Blocked { .. }
+            | Error::DependencyCycle(..)
+            | Error::TransitionNotAllowed { .. }
+            | Error::NotFinished(_) => 1,
+            Error::TaskNotFound(_) => 3,
+            Error::DatabaseChanged => 4,
+            Error::ParseDBError(_) | Error::UnsupportedVersion { .. } => 65,
+            Error::ReadDBError(_) | Error::WatchError(_) | Error::EditorError(_) => 74,
+            #[cfg(feature = "sqlite")]
+            Error::SqliteError(_) => 74,
+            Error::ConfigError { .. } => 78,
+        }
+    }
+}
+
 enum Event<I> {
     Input(I),
     Tick,
+    /// The store was modified on disk
+    Changed,
+}
+
+/// Lets the main thread stop the input thread from reading the terminal
+/// while an external editor owns it
+#[derive(Default)]
+struct InputPause {
+    state: Mutex<PauseState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct PauseState {
+    paused: bool,
+    /// Whether the input thread is polling or reading the terminal
+    polling: bool,
+}
+
+impl InputPause {
+    fn lock(&self) -> MutexGuard<'_, PauseState> {
+        self.state.lock().expect("pause state is never poisoned")
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, PauseState>) -> MutexGuard<'a, PauseState> {
+        self.changed
+            .wait(state)
+            .expect("pause state is never poisoned")
+    }
+
+    /// Stops the input thread, waiting for a poll that is under way to
+    /// finish so it cannot take keys meant for the editor
+    fn pause(&self) {
+        let mut state = self.lock();
+        state.paused = true;
+        while state.polling {
+            state = self.wait(state);
+        }
+    }
+
+    fn resume(&self) {
+        self.lock().paused = false;
+        self.changed.notify_all();
+    }
+
+    /// Called by the input thread before it polls, blocks while paused
+    fn start_poll(&self) {
+        let mut state = self.lock();
+        while state.paused {
+            state = self.wait(state);
+        }
+        state.polling = true;
+    }
+
+    fn end_poll(&self) {
+        self.lock().polling = false;
+        self.changed.notify_all();
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
 enum MenuItem {
     Home,
     Tasks,
+    Lists,
+    Trash,
+    Archive,
 }
 
 impl From<MenuItem> for usize {
@@ -309,6 +1270,9 @@ impl From<MenuItem> for usize {
         match input {
             MenuItem::Home => 0,
             MenuItem::Tasks => 1,
+            MenuItem::Lists => 2,
+            MenuItem::Trash => 3,
+            MenuItem::Archive => 4,
         }
     }
 }
@@ -318,6 +1282,9 @@ impl From<MenuItem> for &str {
         match input {
             MenuItem::Home => "Home",
             MenuItem::Tasks => "Tasks",
+            MenuItem::Lists => "Lists",
+            MenuItem::Trash => "Trash",
+            MenuItem::Archive => "Archive",
         }
     }
 }
@@ -347,88 +1314,6 @@ impl From<UiSections> for &str {
     }
 }
 
-fn collect_tasks(mut file: &File) -> Result<Vec<Task>, Error> {
-    file.seek(SeekFrom::Start(0))?; // Rewind the file before.
-    let mut s = String::new();
-    file.read_to_string(&mut s)?;
-
-    if s == "null" {
-        return Ok(Vec::new());
-    }
-
-    let tasks = match serde_json::from_str(&s) {
-        Ok(tasks) => tasks,
-        Err(e) if e.is_eof() => Vec::new(),
-        Err(e) => Err(e)?,
-    };
-    file.seek(SeekFrom::Start(0))?; // Rewind the file after.
-    Ok(tasks)
-}
-
-fn read_db() -> Result<Vec<Task>, Error> {
-    let db_file = get_db_file()?;
-
-    collect_tasks(&db_file)
-}
-
-fn get_db_file() -> Result<File, Error> {
-    let db_path = find_default_db_file().expect("Task db file should be found!");
-    let db_file = ensure_db_file_exists(db_path)?;
-    Ok(db_file)
-}
-
-fn write_db(mut tasks: Vec<Task>) -> Result<Vec<Task>, Error> {
-    let db_file = get_db_file()?;
-
-    db_file.set_len(0)?;
-    tasks.sort_by(|a, b| a.id.cmp(&b.id));
-    serde_json::to_writer(db_file, &tasks)?;
-    Ok(tasks)
-}
-
-fn add_task_to_db(name: String) -> Result<Vec<Task>, Error> {
-    let mut parsed: Vec<Task> = read_db()?;
-    let new_task = if parsed.len() != 0 {
-        let highest_id = parsed.last().map_or(1, |a| a.id) + 1;
-        Task::create_task(highest_id, name)
-    } else {
-        Task::create_task(1, name)
-    };
-
-    parsed.push(new_task);
-
-    let parsed = write_db(parsed)?;
-    Ok(parsed)
-}
-
-fn progress_task_at_index(task_list_state: &mut ListState) -> Result<(), Error> {
-    if let Some(selected) = task_list_state.selected() {
-        let mut parsed: Vec<Task> = read_db()?;
-        if parsed.len() > 0 {
-            let element = &mut parsed[selected];
-            element.progress();
-            write_db(parsed)?;
-        }
-    }
-
-    Ok(())
-}
-
-fn remove_task_at_index(task_list_state: &mut ListState) -> Result<(), Error> {
-    if let Some(selected) = task_list_state.selected() {
-        let mut parsed: Vec<Task> = read_db()?;
-        if parsed.len() > 0 {
-            parsed.remove(selected);
-            write_db(parsed)?;
-            if selected != 0 {
-                task_list_state.select(Some(selected - 1));
-            }
-        }
-    }
-
-    Ok(())
-}
-
 fn render_home<'a>() -> Paragraph<'a> {
     let home = Paragraph::new(vec![
         Spans::from(vec![Span::raw("")]),
@@ -442,12 +1327,62 @@ fn render_home<'a>() -> Paragraph<'a> {
         )]),
         Spans::from(vec![Span::raw("")]),
         Spans::from(vec![Span::raw("Press 't' to access tasks,")]),
+        Spans::from(vec![Span::raw("'l' to switch between task lists,")]),
         Spans::from(vec![Span::raw("'a' to add random new tasks,")]),
         Spans::from(vec![Span::raw(
             "'p' to progress the currently selected task",
         )]),
+        Spans::from(vec![Span::raw("'r' to rename the currently selected task")]),
+        Spans::from(vec![Span::raw(
+            "'w' to set when the currently selected task is due",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'+' and '-' to raise or lower the priority of the currently selected task",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'o' to order the tasks by priority and due date or by id",
+        )]),
         Spans::from(vec![Span::raw(
-            "'d' to delete the the currently selected task.",
+            "'g' to edit the tags of the currently selected task",
+        )]),
+        Spans::from(vec![Span::raw("'f' to only show tasks with certain tags")]),
+        Spans::from(vec![Span::raw(
+            "'P' to move the currently selected task to another project",
+        )]),
+        Spans::from(vec![Span::raw("Tab to pick a project from the sidebar")]),
+        Spans::from(vec![Span::raw(
+            "'A' to add a subtask to the currently selected task",
+        )]),
+        Spans::from(vec![Span::raw(
+            "Left and Right to collapse or expand subtasks",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'b' to set which tasks the currently selected task depends on",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'n' to edit the notes of the currently selected task, 'N' to edit them in $EDITOR",
+        )]),
+        Spans::from(vec![Span::raw("PageUp and PageDown to scroll the notes")]),
+        Spans::from(vec![Span::raw(
+            "'R' to move the currently selected task back to its previous state",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'s' to move the currently selected task to any state",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'u' to undo the latest change, Ctrl-r to redo it",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'c' to open the trash can, where 'r' restores a task and 'd' purges it",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'x' to archive the currently selected task once it is finished",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'v' to browse the archive, where 'f' searches it and 'r' unarchives a task",
+        )]),
+        Spans::from(vec![Span::raw(
+            "'d' to move the currently selected task to the trash.",
         )]),
     ])
     .alignment(Alignment::Center)
@@ -456,40 +1391,330 @@ fn render_home<'a>() -> Paragraph<'a> {
     home
 }
 
-fn render_tasks<'a>(task_list_state: &ListState) -> (List<'a>, Table<'a>) {
-    let tasks = create_default_table_block(MenuItem::Tasks.into());
+fn render_tasks<'a>(
+    task_list: &[TreeRow<'_>],
+    task_list_state: &ListState,
+    list_name: &str,
+    collapsed: &HashSet<usize>,
+    sort: SortOrder,
+    due_soon: TimeDelta,
+    notes_scroll: u16,
+) -> (List<'a>, Table<'a>, Paragraph<'a>) {
+    let title: &str = MenuItem::Tasks.into();
+    let tasks = create_default_table_block(title).title(match sort {
+        SortOrder::Id => format!("{} ({})", title, list_name),
+        SortOrder::Priority => format!("{} ({}, by priority)", title, list_name),
+    });
 
-    let task_list = read_db().expect("can fetch task list");
+    // Only make room for priority markers once a task has one
+    let show_priority = task_list
+        .iter()
+        .any(|row| row.task.priority != Priority::None);
+    // Likewise for the expand markers once a task has subtasks
+    let show_tree = task_list.iter().any(|row| row.has_children);
+    let now = Utc::now();
     let items: Vec<_> = task_list
         .iter()
-        .map(|task| {
-            ListItem::new(Spans::from(vec![Span::styled(
-                task.name.clone(),
-                Style::default(),
-            )]))
+        .map(|row| {
+            let task = row.task;
+            let style = match task.due_color(now, due_soon) {
+                Some(color) => Style::default().fg(color),
+                None => Style::default(),
+            };
+            let mut spans = Vec::new();
+            if show_priority {
+                let marker = task.priority.marker();
+                let padding = " ".repeat(2 - marker.width());
+                spans.push(marker);
+                spans.push(Span::raw(padding));
+            }
+            if show_tree {
+                let marker = match (row.has_children, collapsed.contains(&task.id)) {
+                    (false, _) => "  ",
+                    (true, false) => "▾ ",
+                    (true, true) => "▸ ",
+                };
+                spans.push(Span::raw(format!("{}{}", "  ".repeat(row.depth), marker)));
+            }
+            spans.push(Span::styled(task.name.clone(), style));
+            if row.blocked {
+                spans.push(Span::styled(" blocked", Style::default().fg(Color::Red)));
+            }
+            if let Some((done, total)) = row.progress {
+                spans.push(Span::styled(
+                    format!(" {}/{} done", done, total),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    let selected_task = task_list
+        .get(task_list_state.selected().unwrap_or(0))
+        .map(|row| row.task);
+
+    let list = List::new(items).block(tasks).highlight_style(
+        Style::default()
+            .bg(Color::Yellow)
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    let task_detail = match selected_task {
+        Some(inner_task) => Table::new(vec![inner_task.create_table_row()])
+            .header(inner_task.create_header_row())
+            .block(create_default_table_block(UiSections::Detail.into()))
+            .widths(inner_task.create_block_constraints()),
+        None => create_empty_table(),
+    };
+
+    let notes = match selected_task {
+        Some(task) if !task.notes.is_empty() => Paragraph::new(task.notes.clone()),
+        Some(_) => Paragraph::new("No notes yet, 'n' to add some")
+            .style(Style::default().fg(Color::DarkGray)),
+        None => Paragraph::new(""),
+    }
+    .block(create_default_table_block("Notes"))
+    .wrap(Wrap { trim: false })
+    .scroll((notes_scroll, 0));
+
+    (list, task_detail, notes)
+}
+
+/// Timeline of the state changes of `task`, newest first, with the time it
+/// spent in each state. Also returns the height it needs.
+fn render_history<'a>(task: Option<&Task>) -> (Paragraph<'a>, u16) {
+    /// Number of state changes shown before older ones are cut off
+    const MAX_LINES: usize = 6;
+
+    let lines: Vec<Spans> = match task {
+        Some(task) if !task.history.is_empty() => (0..task.history.len())
+            .rev()
+            .take(MAX_LINES)
+            .map(|index| {
+                let transition = &task.history[index];
+                let state_style = |state: &TaskState| {
+                    state
+                        .color()
+                        .map_or_else(Style::default, |color| Style::default().fg(color))
+                };
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{}  ", dates::format(transition.at)),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(transition.from.to_string(), state_style(&transition.from)),
+                    Span::raw(" -> "),
+                    Span::styled(transition.to.to_string(), state_style(&transition.to)),
+                    Span::styled(
+                        format!(
+                            "  after {}",
+                            history::format_duration(history::time_in_state(task, index))
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+            })
+            .collect(),
+        Some(_) => vec![Spans::from(Span::styled(
+            "No state changes yet",
+            Style::default().fg(Color::DarkGray),
+        ))],
+        None => Vec::new(),
+    };
+
+    let height = lines.len().max(1) as u16 + 2;
+    let history = Paragraph::new(lines).block(create_default_table_block("History"));
+    (history, height)
+}
+
+/// Popup listing every state, greying out the ones the workflow does not
+/// allow moving a task in `current` to
+fn render_state_picker<'a>(current: &TaskState) -> List<'a> {
+    let items: Vec<_> = (0..TaskState::count())
+        .map(TaskState::at)
+        .map(|state| {
+            let style = if &state != current && !current.allows(&state) {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                state
+                    .color()
+                    .map_or_else(Style::default, |color| Style::default().fg(color))
+            };
+            ListItem::new(Span::styled(state.to_string(), style))
+        })
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Set state (Enter to pick, Esc to cancel)"),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+        )
+}
+
+/// Lists the tasks in the trash with when they were deleted and, if they
+/// are purged on their own, how many days they have left
+fn render_trash<'a>(trash: &[Task], trash_days: u64) -> List<'a> {
+    let now = Utc::now();
+    let items: Vec<_> = trash
+        .iter()
+        .map(|task| {
+            let deleted_at = task.deleted_at.unwrap_or(now);
+            let mut details = format!("deleted {}", dates::format(deleted_at));
+            if trash_days > 0 {
+                let left = trash_days as i64 - (now - deleted_at).num_days();
+                details.push_str(&format!(", purged in {}d", left.max(0)));
+            }
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{:>4}  {}  ", task.id, task.name)),
+                Span::styled(details, Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+
+    List::new(items)
+        .block(create_default_table_block(MenuItem::Trash.into()))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+        )
+}
+
+/// Lists the archived tasks with their project and when they were finished
+fn render_archive<'a>(archived: &[&Task], query: &str) -> List<'a> {
+    let items: Vec<_> = archived
+        .iter()
+        .map(|task| {
+            let mut details = String::new();
+            if let Some(project) = &task.project {
+                details.push_str(&format!("{}  ", project));
+            }
+            if let Some(finished_at) = task.finished_at {
+                details.push_str(&format!("finished {}", dates::format(finished_at)));
+            }
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{:>4}  {}  ", task.id, task.name)),
+                Span::styled(details, Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+
+    let title: &str = MenuItem::Archive.into();
+    let block = if query.is_empty() {
+        create_default_table_block(title)
+    } else {
+        create_default_table_block(title).title(format!("{} (matching '{}')", title, query))
+    };
+    List::new(items).block(block).highlight_style(
+        Style::default()
+            .bg(Color::Yellow)
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD),
+    )
+}
+
+fn render_lists<'a>(lists: &[String], list_name: &str) -> List<'a> {
+    let items: Vec<_> = lists
+        .iter()
+        .map(|name| {
+            let style = if name == list_name {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Spans::from(vec![Span::styled(name.clone(), style)]))
+        })
+        .collect();
+
+    List::new(items)
+        .block(create_default_table_block(MenuItem::Lists.into()))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+        )
+}
+
+/// Tags as coloured chips
+fn tag_chips<'a>(tags: &[String]) -> Spans<'a> {
+    let mut spans = Vec::new();
+    for tag in tags {
+        if !spans.is_empty() {
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::styled(
+            format!(" {} ", tag),
+            Style::default().bg(Color::Blue).fg(Color::White),
+        ));
+    }
+    Spans::from(spans)
+}
+
+/// Project sidebar with the number of tasks in each state of the workflow
+/// per project
+fn render_projects<'a>(rows: &[ProjectRow], focused: bool) -> List<'a> {
+    let items: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            let counts: Vec<String> = row.counts.iter().map(usize::to_string).collect();
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{}{} ", "  ".repeat(row.depth), row.label)),
+                Span::styled(counts.join("/"), Style::default().fg(Color::DarkGray)),
+            ]))
         })
         .collect();
 
-    let selected_task = task_list
-        .get(task_list_state.selected().unwrap_or(0))
-        .map(|f| f.clone());
-
-    let list = List::new(items).block(tasks).highlight_style(
+    let highlight_style = if focused {
         Style::default()
             .bg(Color::Yellow)
             .fg(Color::Black)
-            .add_modifier(Modifier::BOLD),
-    );
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().add_modifier(Modifier::BOLD)
+    };
 
-    let task_detail = match selected_task {
-        Some(inner_task) => Table::new(vec![inner_task.create_table_row()])
-            .header(inner_task.create_header_row())
-            .block(create_default_table_block(UiSections::Detail.into()))
-            .widths(inner_task.create_block_constraints()),
-        None => create_empty_table(),
+    List::new(items)
+        .block(create_default_table_block("Projects"))
+        .highlight_style(highlight_style)
+}
+
+/// Shows which tags the task list is filtered by
+fn render_filter_bar<'a>(tag_filter: &[String]) -> Paragraph<'a> {
+    Paragraph::new(Spans::from(vec![
+        Span::raw("Showing tasks tagged "),
+        Span::styled(
+            tags::format(tag_filter),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(" (f to change)"),
+    ]))
+    .block(create_default_table_block("Filter"))
+}
+
+/// Shows what the due date being typed resolves to
+fn render_due_preview<'a>(input: &str) -> Paragraph<'a> {
+    let (text, color) = match input.trim() {
+        "" => (String::from("no due date"), Color::White),
+        value => match dates::parse(value) {
+            Some(due_at) => (dates::preview(due_at), Color::Green),
+            None => (String::from("not a valid date"), Color::Red),
+        },
     };
 
-    (list, task_detail)
+    Paragraph::new(text)
+        .style(Style::default().fg(color))
+        .block(Block::default().borders(Borders::ALL).title("Resolves to"))
 }
 
 fn create_default_table_block<'a>(title: &'a str) -> Block<'a> {
@@ -510,6 +1735,45 @@ fn create_empty_table<'a>() -> Table<'a> {
         .widths(&[Constraint::Percentage(70)])
 }
 
+/// Moves the selection down by one, wrapping around at the end
+fn select_next(state: &mut ListState, len: usize) {
+    if let Some(selected) = state.selected() {
+        if selected + 1 >= len {
+            state.select(Some(0));
+        } else {
+            state.select(Some(selected + 1));
+        }
+    }
+}
+
+/// Moves the selection up by one, wrapping around at the start
+fn select_previous(state: &mut ListState, len: usize) {
+    if let Some(selected) = state.selected() {
+        if selected > 0 {
+            state.select(Some(selected - 1));
+        } else {
+            state.select(Some(len.saturating_sub(1)));
+        }
+    }
+}
+
+/// Watches the file behind `store` and reports its changes as [`Event::Changed`]
+fn watch_store<I: Send + 'static>(
+    store: &dyn TaskStore,
+    tx: &mpsc::Sender<Event<I>>,
+) -> Result<Option<FileWatcher>, Error> {
+    match store.watch_path() {
+        Some(path) => {
+            let tx = tx.clone();
+            let watcher = watch::watch_file(path, move || {
+                let _ = tx.send(Event::Changed);
+            })?;
+            Ok(Some(watcher))
+        }
+        None => Ok(None),
+    }
+}
+
 /// helper function to create a centered rect using up
 /// certain percentage of the available rect `r`
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -538,28 +1802,86 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    enable_raw_mode().expect("can run in raw mode");
+fn main() {
+    let cli = Cli::parse();
+
+    if let Err(error) = run(cli) {
+        eprintln!("task-tui: {}", error);
+        let code = error.downcast_ref::<Error>().map_or(1, Error::exit_code);
+        process::exit(code);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    // Task states are only known once the workflow is installed
+    let config = Config::load()?;
+    workflow::install(config.workflow.clone())?;
+
+    if let Some(Command::Migrate { from }) = cli.command {
+        let imported = store::migrate_json_to_sqlite(from, cli.db)?;
+        println!("imported {} tasks into the SQLite database", imported);
+        return Ok(());
+    }
+
+    let location = Location::resolve(cli.db, config.backend);
+    let list_name = cli.list.unwrap_or_else(|| DEFAULT_LIST.to_string());
+    if !store::is_valid_list_name(&list_name) {
+        return Err(Error::StringError(format!("invalid list name: {}", list_name)).into());
+    }
+
+    if let Some(number) = cli.restore_backup {
+        let path = location.map(|location| location.path(&list_name));
+        store::restore_backup(&config, path, number)?;
+        println!("restored backup {} of the task database", number);
+        return Ok(());
+    }
+
+    let location =
+        location.ok_or_else(|| Error::StringError(String::from("no task database found")))?;
+    if let Some(command) = cli.command {
+        let mut store = store::open_store(&config, location.path(&list_name))?;
+        commands::run(command, store.as_mut())?;
+        return Ok(());
+    }
+
+    // Create default app state
+    let mut app = App::new(config, location, list_name)?;
 
     let (tx, rx) = mpsc::channel();
+    let mut _watcher = watch_store(app.store.as_ref(), &tx)?;
+    let mut watched_list = app.list_name.clone();
+
+    enable_raw_mode().expect("can run in raw mode");
+
     let tick_rate = Duration::from_millis(200);
+    let input_tx = tx.clone();
+    let input_pause = Arc::new(InputPause::default());
+    let pause = Arc::clone(&input_pause);
     thread::spawn(move || {
+        let tx = input_tx;
         let mut last_tick = Instant::now();
         loop {
             let timeout = tick_rate
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
 
-            if event::poll(timeout).expect("poll works") {
-                if let event::Event::Key(key) = event::read().expect("can read events") {
-                    tx.send(Event::Input(key)).expect("can send events");
+            pause.start_poll();
+            let key = if event::poll(timeout).expect("poll works") {
+                match event::read().expect("can read events") {
+                    event::Event::Key(key) => Some(key),
+                    _ => None,
                 }
+            } else {
+                None
+            };
+            pause.end_poll();
+
+            if let Some(key) = key {
+                tx.send(Event::Input(key)).expect("can send events");
             }
 
-            if last_tick.elapsed() >= tick_rate {
-                if let Ok(_) = tx.send(Event::Tick) {
-                    last_tick = Instant::now();
-                }
+            if last_tick.elapsed() >= tick_rate && tx.send(Event::Tick).is_ok() {
+                last_tick = Instant::now();
             }
         }
     });
@@ -569,16 +1891,69 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    // Create default app state
-    let mut app = App::default();
-
-    let menu_titles = vec!["Home", "Tasks", "Add", "Progress", "Delete", "Exit"];
+    // Each with the index of the letter underlined, the key that opens or does it
+    let menu_titles = [
+        ("Home", 0),
+        ("Tasks", 0),
+        ("Lists", 0),
+        ("Trash can", 6),
+        ("Archive", 5),
+        ("Add", 0),
+        ("Progress", 0),
+        ("Rename", 0),
+        ("Delete", 0),
+        ("Exit", 0),
+    ];
     let mut active_menu_item = MenuItem::Home;
 
     let mut task_list_state = ListState::default();
     task_list_state.select(Some(0));
 
+    let mut lists_state = ListState::default();
+    lists_state.select(Some(0));
+
+    let mut trash_state = ListState::default();
+    trash_state.select(Some(0));
+
+    let mut archive_state = ListState::default();
+    archive_state.select(Some(0));
+
+    // Whether Up and Down move through the project sidebar
+    let mut projects_focused = false;
+
+    // Lines of the notes pane scrolled past, reset when the selection moves
+    let mut notes_scroll: u16 = 0;
+
+    let mut state_picker = ListState::default();
+
     loop {
+        // The selected project may be gone after a change or reload
+        if !projects::tree(&app.tasks)
+            .iter()
+            .any(|row| row.filter == app.project_filter)
+        {
+            app.project_filter = ProjectFilter::All;
+        }
+
+        // The list may have shrunk after a reload
+        let visible_count = app.visible_tasks().len();
+        if let Some(selected) = task_list_state.selected() {
+            if selected >= visible_count {
+                task_list_state.select(Some(visible_count.saturating_sub(1)));
+            }
+        }
+        if let Some(selected) = trash_state.selected() {
+            if selected >= app.trash.len() {
+                trash_state.select(Some(app.trash.len().saturating_sub(1)));
+            }
+        }
+        let archive_count = app.visible_archive().len();
+        if let Some(selected) = archive_state.selected() {
+            if selected >= archive_count {
+                archive_state.select(Some(archive_count.saturating_sub(1)));
+            }
+        }
+
         terminal.draw(|rect| {
             let size = rect.size();
             let chunks = Layout::default()
@@ -588,7 +1963,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     [
                         Constraint::Length(3),
                         Constraint::Min(2),
-                        Constraint::Max(3),
+                        Constraint::Length(3),
                     ]
                     .as_ref(),
                 )
@@ -596,11 +1971,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             let menu = menu_titles
                 .iter()
-                .map(|t| {
-                    let (first, rest) = t.split_at(1);
+                .map(|&(t, key)| {
+                    let (before, rest) = t.split_at(key);
+                    let (key, rest) = rest.split_at(1);
                     Spans::from(vec![
+                        Span::styled(before, Style::default().fg(Color::White)),
                         Span::styled(
-                            first,
+                            key,
                             Style::default()
                                 .fg(Color::Yellow)
                                 .add_modifier(Modifier::UNDERLINED),
@@ -619,8 +1996,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             rect.render_widget(tabs, chunks[0]);
 
-            let copyright = Paragraph::new("task-TUI 2021 - all rights reserved")
-                .style(Style::default().fg(Color::LightCyan))
+            let copyright = match &app.status {
+                Some(Status::Info(message)) => {
+                    Paragraph::new(message.clone()).style(Style::default().fg(Color::Yellow))
+                }
+                Some(Status::Error(message)) => {
+                    Paragraph::new(message.clone()).style(Style::default().fg(Color::Red))
+                }
+                None => Paragraph::new("task-TUI 2021 - all rights reserved")
+                    .style(Style::default().fg(Color::LightCyan)),
+            };
+            let copyright = copyright
                 .alignment(Alignment::Center)
                 .block(create_default_table_block(UiSections::Copyright.into()));
 
@@ -629,114 +2015,718 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             match active_menu_item {
                 MenuItem::Home => rect.render_widget(render_home(), chunks[1]),
                 MenuItem::Tasks => {
+                    let mut tasks_area = chunks[1];
+                    if !app.tag_filter.is_empty() {
+                        let filter_chunks = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Length(3), Constraint::Min(2)].as_ref())
+                            .split(tasks_area);
+                        rect.render_widget(render_filter_bar(&app.tag_filter), filter_chunks[0]);
+                        tasks_area = filter_chunks[1];
+                    }
+
                     let task_chunks = Layout::default()
                         .direction(Direction::Horizontal)
                         .constraints(
-                            [Constraint::Percentage(20), Constraint::Percentage(80)].as_ref(),
+                            [
+                                Constraint::Percentage(20),
+                                Constraint::Percentage(20),
+                                Constraint::Percentage(60),
+                            ]
+                            .as_ref(),
+                        )
+                        .split(tasks_area);
+
+                    let project_rows = projects::tree(&app.tasks);
+                    let mut project_state = ListState::default();
+                    project_state.select(
+                        project_rows
+                            .iter()
+                            .position(|row| row.filter == app.project_filter),
+                    );
+                    rect.render_stateful_widget(
+                        render_projects(&project_rows, projects_focused),
+                        task_chunks[0],
+                        &mut project_state,
+                    );
+
+                    let rows = app.visible_tasks();
+                    let selected_task = rows
+                        .get(task_list_state.selected().unwrap_or(0))
+                        .map(|row| row.task);
+                    let (history, history_height) = render_history(selected_task);
+                    let (left, right, notes) = render_tasks(
+                        &rows,
+                        &task_list_state,
+                        &app.list_name,
+                        &app.collapsed,
+                        app.sort,
+                        app.config.due_soon(),
+                        notes_scroll,
+                    );
+                    let detail_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints(
+                            [
+                                Constraint::Length(4),
+                                Constraint::Length(history_height),
+                                Constraint::Min(3),
+                            ]
+                            .as_ref(),
                         )
-                        .split(chunks[1]);
-                    let (left, right) = render_tasks(&task_list_state);
-                    rect.render_stateful_widget(left, task_chunks[0], &mut task_list_state);
-                    rect.render_widget(right, task_chunks[1]);
+                        .split(task_chunks[2]);
+                    rect.render_stateful_widget(left, task_chunks[1], &mut task_list_state);
+                    rect.render_widget(right, detail_chunks[0]);
+                    rect.render_widget(history, detail_chunks[1]);
+                    rect.render_widget(notes, detail_chunks[2]);
+                }
+                MenuItem::Lists => {
+                    let lists = render_lists(&app.lists, &app.list_name);
+                    rect.render_stateful_widget(lists, chunks[1], &mut lists_state);
+                }
+                MenuItem::Trash => {
+                    let trash = render_trash(&app.trash, app.config.trash_days);
+                    rect.render_stateful_widget(trash, chunks[1], &mut trash_state);
+                }
+                MenuItem::Archive => {
+                    let archived = render_archive(&app.visible_archive(), &app.archive_query);
+                    rect.render_stateful_widget(archived, chunks[1], &mut archive_state);
                 }
             }
 
-            if app.input_mode == InputMode::Editing {
-                //let block = Block::default().title("Popup").borders(Borders::ALL);
-                let input = Paragraph::new(app.input.as_ref())
-                    .style(match app.input_mode {
-                        InputMode::Normal => Style::default(),
-                        InputMode::Editing => Style::default().fg(Color::Yellow),
-                    })
-                    .block(Block::default().borders(Borders::ALL).title("Input"));
+            if app.input_mode == InputMode::ReloadPrompt {
+                let prompt = Paragraph::new(
+                    "Database changed on disk, reload? (y: reload, n: keep my changes)",
+                )
+                .style(Style::default().fg(Color::Yellow))
+                .alignment(Alignment::Center)
+                .block(Block::default().borders(Borders::ALL).title("Conflict"));
 
                 let area = centered_rect(60, 10, size);
-                rect.render_widget(Clear, area); //this clears out the background
-                rect.render_widget(input, area);
+                rect.render_widget(Clear, area);
+                rect.render_widget(prompt, area);
             }
 
-            match app.input_mode {
-                InputMode::Normal =>
-                    // Hide the cursor. `Frame` does this by default, so we don't need to do anything here
-                    {}
+            let confirmation = match app.input_mode {
+                InputMode::ConfirmingDelete(id) => app
+                    .tasks
+                    .iter()
+                    .find(|task| task.id == id)
+                    .map(|task| ("Delete", format!("Move '{}' to the trash?", task.name))),
+                InputMode::ConfirmingPurge(id) => app
+                    .trash
+                    .iter()
+                    .find(|task| task.id == id)
+                    .map(|task| ("Purge", format!("Delete '{}' for good?", task.name))),
+                _ => None,
+            };
+            if let Some((title, question)) = confirmation {
+                let prompt = Paragraph::new(format!("{} (y: yes, n: no)", question))
+                    .style(Style::default().fg(Color::Yellow))
+                    .alignment(Alignment::Center)
+                    .block(Block::default().borders(Borders::ALL).title(title));
 
-                InputMode::Editing => {
-                    let area = centered_rect(60, 10, size);
+                let area = centered_rect(60, 10, size);
+                rect.render_widget(Clear, area);
+                rect.render_widget(prompt, area);
+            }
 
-                    // Make the cursor visible and ask tui-rs to put it at the specified coordinates after rendering
-                    rect.set_cursor(
-                        // Put cursor past the end of the input text
-                        area.x + app.input.width() as u16 + 1,
-                        // Move one line down, from the border to the input line
-                        area.y + 1,
+            if let InputMode::EditingNotes(_) = app.input_mode {
+                let area = centered_rect(70, 60, size);
+                // Keep the line with the cursor in view
+                let visible_lines = area.height.saturating_sub(2) as usize;
+                let line = app.input.cursor_line();
+                let scroll = (line + 1).saturating_sub(visible_lines);
+                let editor = Paragraph::new(app.input.value())
+                    .style(Style::default().fg(Color::Yellow))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Notes (Ctrl-s to save, Esc to cancel)"),
                     )
+                    .scroll((scroll as u16, 0));
+
+                rect.render_widget(Clear, area);
+                rect.render_widget(editor, area);
+                rect.set_cursor(
+                    area.x + app.input.cursor_width() as u16 + 1,
+                    area.y + (line - scroll) as u16 + 1,
+                );
+            }
+
+            if let InputMode::PickingState(id) = app.input_mode {
+                if let Some(task) = app.tasks.iter().find(|task| task.id == id) {
+                    let area = centered_rect(30, 30, size);
+                    rect.render_widget(Clear, area);
+                    rect.render_stateful_widget(
+                        render_state_picker(&task.state),
+                        area,
+                        &mut state_picker,
+                    );
+                }
+            }
+
+            let input_title = match app.input_mode {
+                InputMode::Normal
+                | InputMode::ReloadPrompt
+                | InputMode::EditingNotes(_)
+                | InputMode::PickingState(_)
+                | InputMode::ConfirmingDelete(_)
+                | InputMode::ConfirmingPurge(_) => None,
+                InputMode::Editing => Some("Input"),
+                InputMode::Renaming(_) => Some("Rename"),
+                InputMode::NamingList => Some("New list"),
+                InputMode::SettingDue(_) => {
+                    Some("Due date (e.g. tomorrow, fri 9:00, in 3d, eom; empty to clear)")
+                }
+                InputMode::Tagging(_) => Some("Tags (e.g. +backend +release)"),
+                InputMode::Filtering => Some("Show tasks with any of the tags (empty for all)"),
+                InputMode::SearchingArchive => Some("Search the archive (empty for all)"),
+                InputMode::SettingProject(_) => {
+                    Some("Project (e.g. work.infra.ci; empty for none)")
+                }
+                InputMode::AddingSubtask(_) => Some("Subtask"),
+                InputMode::SettingDependencies(_) => {
+                    Some("Depends on (task ids, e.g. #3 #7; empty for none)")
+                }
+            };
+
+            if let Some(title) = input_title {
+                //let block = Block::default().title("Popup").borders(Borders::ALL);
+                let input = Paragraph::new(app.input.value())
+                    .style(Style::default().fg(Color::Yellow))
+                    .block(Block::default().borders(Borders::ALL).title(title));
+
+                let area = centered_rect(60, 10, size);
+                rect.render_widget(Clear, area); //this clears out the background
+                rect.render_widget(input, area);
+
+                // Make the cursor visible and ask tui-rs to put it at the specified coordinates after rendering
+                rect.set_cursor(
+                    // Put cursor behind the character it is currently placed at
+                    area.x + app.input.cursor_width() as u16 + 1,
+                    // Move one line down, from the border to the input line
+                    area.y + 1,
+                );
+
+                if let InputMode::SettingDue(_) = app.input_mode {
+                    let preview_area = Rect {
+                        y: area.bottom(),
+                        height: 3,
+                        ..area
+                    }
+                    .intersection(size);
+                    rect.render_widget(Clear, preview_area);
+                    rect.render_widget(render_due_preview(app.input.value()), preview_area);
                 }
             }
         })?;
 
-        match rx.recv()? {
+        // Changes may reorder the tasks, unless the selection was moved on
+        // purpose it follows the selected task
+        let selected = task_list_state.selected();
+        let selected_id =
+            selected.and_then(|selected| app.visible_tasks().get(selected).map(|row| row.task.id));
+
+        let mut outcome = Ok(());
+        let event = rx.recv()?;
+        if let Event::Input(_) = event {
+            app.status = None;
+        }
+        match event {
             Event::Input(event) => match app.input_mode {
                 InputMode::Normal => {
                     match event.code {
-                        KeyCode::Char('e') => {
-                            disable_raw_mode()?;
-                            terminal.show_cursor()?;
-                            terminal.clear()?;
-                            break;
-                        }
+                        KeyCode::Char('e') => match app.flush() {
+                            Ok(()) => {
+                                disable_raw_mode()?;
+                                terminal.show_cursor()?;
+                                terminal.clear()?;
+                                break;
+                            }
+                            Err(error) => outcome = Err(error),
+                        },
                         KeyCode::Char('h') => active_menu_item = MenuItem::Home,
                         KeyCode::Char('t') => active_menu_item = MenuItem::Tasks,
+                        KeyCode::Char('c') => active_menu_item = MenuItem::Trash,
+                        KeyCode::Char('v') => active_menu_item = MenuItem::Archive,
+                        KeyCode::Char('l') => {
+                            active_menu_item = MenuItem::Lists;
+                            outcome = app.refresh_lists();
+                        }
                         KeyCode::Char('a') => {
-                            app.input_mode = InputMode::Editing;
+                            app.input_mode = match active_menu_item {
+                                MenuItem::Lists => InputMode::NamingList,
+                                _ => InputMode::Editing,
+                            };
                             //add_task_to_db()?;
                         }
+                        KeyCode::Enter => {
+                            if let MenuItem::Lists = active_menu_item {
+                                let selected = lists_state.selected().unwrap_or(0);
+                                if let Some(name) = app.lists.get(selected).cloned() {
+                                    outcome = app.open_list(name);
+                                    task_list_state.select(Some(0));
+                                    active_menu_item = MenuItem::Tasks;
+                                }
+                            }
+                        }
                         KeyCode::Char('p') => {
-                            progress_task_at_index(&mut task_list_state)?;
+                            if let Some(id) = selected_id {
+                                outcome = app.progress_task(id);
+                            }
+                        }
+                        KeyCode::Char('b') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                let depends_on = app
+                                    .tasks
+                                    .iter()
+                                    .find(|task| task.id == id)
+                                    .map(|task| dependencies::format(&task.depends_on))
+                                    .unwrap_or_default();
+                                app.input = Input::with_value(depends_on);
+                                app.input_mode = InputMode::SettingDependencies(id);
+                            }
                         }
-                        KeyCode::Char('d') => {
-                            remove_task_at_index(&mut task_list_state)?;
+                        KeyCode::Char('n') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                let notes = app
+                                    .tasks
+                                    .iter()
+                                    .find(|task| task.id == id)
+                                    .map(|task| task.notes.clone())
+                                    .unwrap_or_default();
+                                app.input = Input::with_value(notes);
+                                app.input_mode = InputMode::EditingNotes(id);
+                            }
                         }
-                        KeyCode::Down => {
-                            if let Some(selected) = task_list_state.selected() {
-                                let amount_task = read_db().expect("can fetch task list").len();
-                                if selected >= amount_task - 1 {
-                                    task_list_state.select(Some(0));
-                                } else {
-                                    task_list_state.select(Some(selected + 1));
+                        KeyCode::Char('N') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                let notes = app
+                                    .tasks
+                                    .iter()
+                                    .find(|task| task.id == id)
+                                    .map(|task| task.notes.clone())
+                                    .unwrap_or_default();
+
+                                input_pause.pause();
+                                terminal.clear()?;
+                                terminal.show_cursor()?;
+                                disable_raw_mode()?;
+                                let edited = notes::edit_externally(id, &notes);
+                                enable_raw_mode()?;
+                                input_pause.resume();
+                                // Everything has to be drawn again
+                                terminal.clear()?;
+
+                                outcome = edited.and_then(|notes| {
+                                    app.update_task(id, |task| task.notes = notes)
+                                });
+                            }
+                        }
+                        KeyCode::PageDown => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                let lines = app
+                                    .tasks
+                                    .iter()
+                                    .find(|task| task.id == id)
+                                    .map_or(0, |task| task.notes.lines().count());
+                                notes_scroll =
+                                    (notes_scroll + 5).min(lines.saturating_sub(1) as u16);
+                            }
+                        }
+                        KeyCode::PageUp => notes_scroll = notes_scroll.saturating_sub(5),
+                        KeyCode::Char('R') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                outcome = app.regress_task(id);
+                            }
+                        }
+                        KeyCode::Char('s') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                let index = app
+                                    .tasks
+                                    .iter()
+                                    .find(|task| task.id == id)
+                                    .map_or(0, |task| task.state.index());
+                                state_picker.select(Some(index));
+                                app.input_mode = InputMode::PickingState(id);
+                            }
+                        }
+                        KeyCode::Char('A') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                app.input_mode = InputMode::AddingSubtask(id);
+                            }
+                        }
+                        KeyCode::Right => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                app.collapsed.remove(&id);
+                            }
+                        }
+                        KeyCode::Left => {
+                            if let (MenuItem::Tasks, Some(index)) = (active_menu_item, selected) {
+                                if let Some(parent) = app.collapse_or_find_parent(index) {
+                                    task_list_state.select(Some(parent));
+                                }
+                            }
+                        }
+                        KeyCode::Char('u') => outcome = app.undo(),
+                        KeyCode::Char('r') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                            outcome = app.redo();
+                        }
+                        KeyCode::Char('r') if matches!(active_menu_item, MenuItem::Archive) => {
+                            let selected = archive_state.selected().unwrap_or(0);
+                            if let Some(id) =
+                                app.visible_archive().get(selected).map(|task| task.id)
+                            {
+                                outcome = app.unarchive_task(id);
+                            }
+                        }
+                        KeyCode::Char('r') if matches!(active_menu_item, MenuItem::Trash) => {
+                            let selected = trash_state.selected().unwrap_or(0);
+                            if let Some(id) = app.trash.get(selected).map(|task| task.id) {
+                                outcome = app.restore_task(id);
+                            }
+                        }
+                        KeyCode::Char('r') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                if let Some(task) = app.tasks.iter().find(|task| task.id == id) {
+                                    app.input = Input::with_value(task.name.clone());
+                                    app.input_mode = InputMode::Renaming(id);
+                                }
+                            }
+                        }
+                        KeyCode::Char('w') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                if let Some(task) = app.tasks.iter().find(|task| task.id == id) {
+                                    let due = task.due_at.map(dates::format).unwrap_or_default();
+                                    app.input = Input::with_value(due);
+                                    app.input_mode = InputMode::SettingDue(id);
+                                }
+                            }
+                        }
+                        KeyCode::Char('g') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                if let Some(task) = app.tasks.iter().find(|task| task.id == id) {
+                                    app.input = Input::with_value(tags::format(&task.tags));
+                                    app.input_mode = InputMode::Tagging(id);
                                 }
                             }
                         }
-                        KeyCode::Up => {
-                            if let Some(selected) = task_list_state.selected() {
-                                let amount_task = read_db().expect("can fetch task list").len();
-                                if selected > 0 {
-                                    task_list_state.select(Some(selected - 1));
-                                } else {
-                                    task_list_state.select(Some(amount_task - 1));
+                        KeyCode::Char('P') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                if let Some(task) = app.tasks.iter().find(|task| task.id == id) {
+                                    app.input =
+                                        Input::with_value(task.project.clone().unwrap_or_default());
+                                    app.input_mode = InputMode::SettingProject(id);
                                 }
                             }
                         }
+                        KeyCode::Tab => {
+                            if let MenuItem::Tasks = active_menu_item {
+                                projects_focused = !projects_focused;
+                            }
+                        }
+                        KeyCode::Char('f') => match active_menu_item {
+                            MenuItem::Tasks => {
+                                app.input = Input::with_value(tags::format(&app.tag_filter));
+                                app.input_mode = InputMode::Filtering;
+                            }
+                            MenuItem::Archive => {
+                                app.input = Input::with_value(app.archive_query.clone());
+                                app.input_mode = InputMode::SearchingArchive;
+                            }
+                            _ => {}
+                        },
+                        KeyCode::Char('x') => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                outcome = app.archive_task(id);
+                            }
+                        }
+                        KeyCode::Char(c @ ('+' | '-')) => {
+                            if let (MenuItem::Tasks, Some(id)) = (active_menu_item, selected_id) {
+                                outcome = app.update_task(id, |task| {
+                                    task.priority = if c == '+' {
+                                        task.priority.raise()
+                                    } else {
+                                        task.priority.lower()
+                                    }
+                                });
+                            }
+                        }
+                        KeyCode::Char('o') => app.toggle_sort(),
+                        KeyCode::Char('d') => match active_menu_item {
+                            MenuItem::Trash => {
+                                let selected = trash_state.selected().unwrap_or(0);
+                                if let Some(task) = app.trash.get(selected) {
+                                    app.input_mode = InputMode::ConfirmingPurge(task.id);
+                                }
+                            }
+                            MenuItem::Tasks => {
+                                if let Some(id) = selected_id {
+                                    app.input_mode = InputMode::ConfirmingDelete(id);
+                                }
+                            }
+                            _ => {}
+                        },
+                        KeyCode::Down => match active_menu_item {
+                            MenuItem::Lists => select_next(&mut lists_state, app.lists.len()),
+                            MenuItem::Trash => select_next(&mut trash_state, app.trash.len()),
+                            MenuItem::Archive => select_next(&mut archive_state, archive_count),
+                            MenuItem::Tasks if projects_focused => {
+                                app.move_project_selection(true);
+                                task_list_state.select(Some(0));
+                            }
+                            _ => select_next(&mut task_list_state, visible_count),
+                        },
+                        KeyCode::Up => match active_menu_item {
+                            MenuItem::Lists => select_previous(&mut lists_state, app.lists.len()),
+                            MenuItem::Trash => select_previous(&mut trash_state, app.trash.len()),
+                            MenuItem::Archive => select_previous(&mut archive_state, archive_count),
+                            MenuItem::Tasks if projects_focused => {
+                                app.move_project_selection(false);
+                                task_list_state.select(Some(0));
+                            }
+                            _ => select_previous(&mut task_list_state, visible_count),
+                        },
                         _ => {}
                     }
                 }
                 InputMode::Editing => match event.code {
+                    // A blank name keeps the popup open, tasks cannot be nameless
+                    KeyCode::Enter if !app.input.value().trim().is_empty() => {
+                        app.input_mode = InputMode::Normal;
+                        let name = app.input.take();
+                        outcome = app.add_task(name, None);
+                    }
+                    KeyCode::Enter => {}
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::Renaming(id) => match event.code {
+                    // A blank name keeps the popup open, tasks cannot be nameless
+                    KeyCode::Enter if !app.input.value().trim().is_empty() => {
+                        app.input_mode = InputMode::Normal;
+                        let name = app.input.take();
+                        outcome = app.update_task(id, |task| task.rename(name));
+                    }
+                    KeyCode::Enter => {}
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::NamingList => match event.code {
+                    // An invalid name keeps the popup open for correction
+                    KeyCode::Enter if store::is_valid_list_name(app.input.value()) => {
+                        app.input_mode = InputMode::Normal;
+                        let name = app.input.take();
+                        outcome = app.open_list(name);
+                        task_list_state.select(Some(0));
+                        active_menu_item = MenuItem::Tasks;
+                    }
+                    KeyCode::Enter => {
+                        app.status = Some(Status::Error(String::from(
+                            "list names may only contain letters, digits, '-' and '_'",
+                        )));
+                    }
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::SettingDue(id) => match event.code {
+                    KeyCode::Enter => {
+                        // An invalid date keeps the popup open for correction
+                        let due_at = match app.input.value().trim() {
+                            "" => Some(None),
+                            value => dates::parse(value).map(Some),
+                        };
+                        if let Some(due_at) = due_at {
+                            app.input.take();
+                            app.input_mode = InputMode::Normal;
+                            outcome = app.update_task(id, |task| task.due_at = due_at);
+                        }
+                    }
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::Tagging(id) => match event.code {
+                    KeyCode::Enter => {
+                        app.input_mode = InputMode::Normal;
+                        let tags = tags::parse(&app.input.take());
+                        outcome = app.update_task(id, |task| task.tags = tags);
+                    }
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::AddingSubtask(parent_id) => match event.code {
+                    // A blank name keeps the popup open, tasks cannot be nameless
+                    KeyCode::Enter if !app.input.value().trim().is_empty() => {
+                        app.input_mode = InputMode::Normal;
+                        let name = app.input.take();
+                        outcome = app.add_task(name, Some(parent_id));
+                    }
+                    KeyCode::Enter => {}
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::SettingDependencies(id) => match event.code {
+                    KeyCode::Enter => {
+                        // Anything but task ids keeps the popup open for correction
+                        if let Some(depends_on) = dependencies::parse(app.input.value()) {
+                            app.input.take();
+                            app.input_mode = InputMode::Normal;
+                            outcome = app.set_dependencies(id, depends_on);
+                        }
+                    }
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::PickingState(id) => match event.code {
+                    KeyCode::Enter => {
+                        app.input_mode = InputMode::Normal;
+                        let index = state_picker.selected().unwrap_or(0);
+                        if let Ok(state) = TaskState::try_from(index) {
+                            outcome = app.set_task_state(id, state);
+                        }
+                    }
+                    KeyCode::Down => select_next(&mut state_picker, TaskState::count()),
+                    KeyCode::Up => select_previous(&mut state_picker, TaskState::count()),
+                    KeyCode::Esc => app.input_mode = InputMode::Normal,
+                    _ => {}
+                },
+                InputMode::EditingNotes(id) => match event.code {
+                    KeyCode::Char('s') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.input_mode = InputMode::Normal;
+                        let notes = notes::clean(&app.input.take());
+                        outcome = app.update_task(id, |task| task.notes = notes);
+                    }
+                    KeyCode::Enter => app.input.insert('\n'),
+                    KeyCode::Up => app.input.move_up(),
+                    KeyCode::Down => app.input.move_down(),
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::SettingProject(id) => match event.code {
+                    KeyCode::Enter => {
+                        app.input_mode = InputMode::Normal;
+                        let project = projects::normalize(&app.input.take());
+                        outcome = app.update_task(id, |task| task.project = project);
+                    }
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::Filtering => match event.code {
                     KeyCode::Enter => {
-                        add_task_to_db(app.input.drain(..).collect())?;
                         app.input_mode = InputMode::Normal;
+                        app.tag_filter = tags::parse(&app.input.take());
+                        task_list_state.select(Some(0));
                     }
-                    KeyCode::Char(c) => {
-                        app.input.push(c);
+                    KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
                     }
-                    KeyCode::Backspace => {
-                        app.input.pop();
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::SearchingArchive => match event.code {
+                    KeyCode::Enter => {
+                        app.input_mode = InputMode::Normal;
+                        app.archive_query = app.input.take().trim().to_string();
+                        archive_state.select(Some(0));
                     }
                     KeyCode::Esc => {
+                        app.input.take();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => app.handle_input_key(event),
+                },
+                InputMode::ConfirmingDelete(id) => match event.code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        app.input_mode = InputMode::Normal;
+                        outcome = app.trash_task(id);
+                        if let Some(index) = selected.filter(|&index| index > 0) {
+                            task_list_state.select(Some(index - 1));
+                        }
+                    }
+                    KeyCode::Char('n') | KeyCode::Esc => app.input_mode = InputMode::Normal,
+                    _ => {}
+                },
+                InputMode::ConfirmingPurge(id) => match event.code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
                         app.input_mode = InputMode::Normal;
+                        outcome = app.purge_task(id);
                     }
+                    KeyCode::Char('n') | KeyCode::Esc => app.input_mode = InputMode::Normal,
+                    _ => {}
+                },
+                InputMode::ReloadPrompt => match event.code {
+                    KeyCode::Char('y') => outcome = app.resolve_conflict(true),
+                    KeyCode::Char('n') => outcome = app.resolve_conflict(false),
                     _ => {}
                 },
             },
-            Event::Tick => {}
+            Event::Tick => {
+                if app.input_mode != InputMode::ReloadPrompt {
+                    outcome = app.flush_if_due();
+                }
+            }
+            Event::Changed => {
+                // Unwritten changes are reconciled through the reload prompt
+                // once they are flushed. A file caught halfway through being
+                // written by another program is picked up by its next event.
+                if app.pending.is_empty() {
+                    outcome = match app.reload() {
+                        Err(Error::ParseDBError(_)) => Ok(()),
+                        result => result,
+                    };
+                }
+            }
+        }
+
+        if let Err(error) = outcome {
+            app.handle_error(error)?;
+        }
+
+        if watched_list == app.list_name {
+            let index = selected_id
+                .filter(|_| task_list_state.selected() == selected)
+                .and_then(|id| app.visible_tasks().iter().position(|row| row.task.id == id));
+            if let Some(index) = index {
+                task_list_state.select(Some(index));
+            }
+
+            let now_selected_id = task_list_state
+                .selected()
+                .and_then(|selected| app.visible_tasks().get(selected).map(|row| row.task.id));
+            if now_selected_id != selected_id {
+                notes_scroll = 0;
+            }
+        } else {
+            _watcher = watch_store(app.store.as_ref(), &tx)?;
+            watched_list = app.list_name.clone();
         }
     }
 
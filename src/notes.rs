@@ -0,0 +1,84 @@
+//! Notes are free-form text attached to a task, edited either in the notes
+//! popup or in the editor named by `$VISUAL` or `$EDITOR`.
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::Error;
+
+/// Editor used when neither `$VISUAL` nor `$EDITOR` is set
+const DEFAULT_EDITOR: &str = "vi";
+
+/// How often to pick another name when a temporary file name is taken
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+/// Creates a file for the notes of the task with the given id in the shared
+/// temporary directory. It gets a random name and is only readable by the
+/// user, and it must not exist yet, so nobody else can read the notes or
+/// plant a file or symlink for them in advance.
+fn create_temp_file(id: usize) -> io::Result<(PathBuf, File)> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    for _ in 0..TEMP_FILE_ATTEMPTS {
+        let name = format!("task-tui-notes-{}-{:016x}.txt", id, rand::random::<u64>());
+        let path = env::temp_dir().join(name);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "no unused name for a temporary file found",
+    ))
+}
+
+/// Lets the user edit `notes` of the task with the given id in their editor
+/// and returns the result. The terminal has to be out of raw mode meanwhile.
+pub fn edit_externally(id: usize, notes: &str) -> Result<String, Error> {
+    let (path, mut file) = create_temp_file(id).map_err(Error::EditorError)?;
+    let edited = file
+        .write_all(notes.as_bytes())
+        .and_then(|()| run_editor(&path))
+        .and_then(|()| fs::read_to_string(&path));
+    drop(file);
+    // The file only ever held a copy of the notes
+    let _ = fs::remove_file(&path);
+
+    edited
+        .map(|notes| clean(&notes))
+        .map_err(Error::EditorError)
+}
+
+fn run_editor(path: &Path) -> io::Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+    // The variable may carry arguments, like `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_EDITOR);
+
+    let status = Command::new(program).args(words).arg(path).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} exited with {}",
+            editor, status
+        )))
+    }
+}
+
+/// Drops the trailing whitespace editors tend to leave behind
+pub fn clean(notes: &str) -> String {
+    notes.trim_end().to_string()
+}
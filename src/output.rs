@@ -0,0 +1,254 @@
+//! Machine-readable output of task records for the `list` command.
+//!
+//! The field names and value formats below are a stable interface for
+//! scripts and must not change with the layout of the DB file.
+
+use std::io::{self, Write};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use csv::QuoteStyle;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::{Error, Task};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// Plain text for reading in a terminal
+    Text,
+    Json,
+    Csv,
+    Tsv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Field {
+    Id,
+    Name,
+    State,
+    #[value(name = "created_at")]
+    CreatedAt,
+    #[value(name = "started_at")]
+    StartedAt,
+    #[value(name = "finished_at")]
+    FinishedAt,
+    #[value(name = "due_at")]
+    DueAt,
+    Priority,
+    Tags,
+    Project,
+    #[value(name = "parent_id")]
+    ParentId,
+    #[value(name = "depends_on")]
+    DependsOn,
+    Notes,
+    History,
+    #[value(name = "deleted_at")]
+    DeletedAt,
+}
+
+pub const ALL_FIELDS: &[Field] = &[
+    Field::Id,
+    Field::Name,
+    Field::State,
+    Field::CreatedAt,
+    Field::StartedAt,
+    Field::FinishedAt,
+    Field::DueAt,
+    Field::Priority,
+    Field::Tags,
+    Field::Project,
+    Field::ParentId,
+    Field::DependsOn,
+    Field::Notes,
+    Field::History,
+    Field::DeletedAt,
+];
+
+fn format_timestamp(timestamp: Option<DateTime<Utc>>) -> Option<String> {
+    timestamp.map(|timestamp| timestamp.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Name => "name",
+            Field::State => "state",
+            Field::CreatedAt => "created_at",
+            Field::StartedAt => "started_at",
+            Field::FinishedAt => "finished_at",
+            Field::DueAt => "due_at",
+            Field::Priority => "priority",
+            Field::Tags => "tags",
+            Field::Project => "project",
+            Field::ParentId => "parent_id",
+            Field::DependsOn => "depends_on",
+            Field::Notes => "notes",
+            Field::History => "history",
+            Field::DeletedAt => "deleted_at",
+        }
+    }
+
+    fn json_value(self, task: &Task) -> Value {
+        match self {
+            Field::Id => Value::from(task.id),
+            Field::Tags => Value::from(task.tags.clone()),
+            Field::ParentId => task.parent_id.map_or(Value::Null, Value::from),
+            Field::DependsOn => Value::from(task.depends_on.clone()),
+            Field::History => Value::from(
+                task.history
+                    .iter()
+                    .map(|transition| {
+                        let mut entry = Map::new();
+                        entry.insert(
+                            String::from("from"),
+                            Value::from(transition.from.to_string()),
+                        );
+                        entry.insert(String::from("to"), Value::from(transition.to.to_string()));
+                        entry.insert(
+                            String::from("at"),
+                            Value::from(format_timestamp(Some(transition.at))),
+                        );
+                        Value::Object(entry)
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            _ => self.text_value(task).map_or(Value::Null, Value::from),
+        }
+    }
+
+    /// Value of the field as text, `None` for unset timestamps, projects and
+    /// parents
+    fn text_value(self, task: &Task) -> Option<String> {
+        match self {
+            Field::Id => Some(task.id.to_string()),
+            Field::Name => Some(task.name.clone()),
+            Field::State => Some(task.state.to_string()),
+            Field::CreatedAt => format_timestamp(Some(task.created_at)),
+            Field::StartedAt => format_timestamp(task.started_at),
+            Field::FinishedAt => format_timestamp(task.finished_at),
+            Field::DueAt => format_timestamp(task.due_at),
+            Field::Priority => Some(task.priority.to_string()),
+            Field::Tags => Some(task.tags.join(",")),
+            Field::Project => task.project.clone(),
+            Field::ParentId => task.parent_id.map(|id| id.to_string()),
+            Field::DependsOn => Some(
+                task.depends_on
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            Field::Notes => Some(task.notes.clone()),
+            Field::History => Some(
+                task.history
+                    .iter()
+                    .map(|transition| {
+                        format!(
+                            "{} {} -> {}",
+                            format_timestamp(Some(transition.at)).unwrap_or_default(),
+                            transition.from,
+                            transition.to
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+            Field::DeletedAt => format_timestamp(task.deleted_at),
+        }
+    }
+}
+
+/// Writes CSV, or TSV when `tsv` is set. TSV has no quoting, so tabs and
+/// line breaks inside values are replaced by spaces there.
+fn write_delimited(
+    tasks: &[Task],
+    fields: &[Field],
+    tsv: bool,
+    out: impl Write,
+) -> Result<(), csv::Error> {
+    let mut builder = csv::WriterBuilder::new();
+    if tsv {
+        builder.delimiter(b'\t').quote_style(QuoteStyle::Never);
+    }
+    let mut writer = builder.from_writer(out);
+
+    writer.write_record(fields.iter().map(|field| field.name()))?;
+    for task in tasks {
+        writer.write_record(fields.iter().map(|field| {
+            let value = field.text_value(task).unwrap_or_default();
+            if tsv {
+                value.replace(['\t', '\n', '\r'], " ")
+            } else {
+                value
+            }
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// A task as a JSON object of the given fields, in their order
+struct Record<'a> {
+    task: &'a Task,
+    fields: &'a [Field],
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut record = serializer.serialize_map(Some(self.fields.len()))?;
+        for field in self.fields {
+            record.serialize_entry(field.name(), &field.json_value(self.task))?;
+        }
+        record.end()
+    }
+}
+
+/// Writes `tasks` to `out`, restricted to `fields` in the given order
+pub fn write_tasks(
+    tasks: &[Task],
+    format: Format,
+    fields: &[Field],
+    mut out: impl Write,
+) -> Result<(), Error> {
+    match format {
+        Format::Text => {
+            for task in tasks {
+                let columns: Vec<_> = fields
+                    .iter()
+                    .map(|field| field.text_value(task).unwrap_or_else(|| "-".to_string()))
+                    .collect();
+                writeln!(out, "{}", columns.join("  "))?;
+            }
+        }
+        Format::Json => {
+            let records: Vec<_> = tasks.iter().map(|task| Record { task, fields }).collect();
+            serde_json::to_writer(&mut out, &records)?;
+            writeln!(out)?;
+        }
+        Format::Csv => write_delimited(tasks, fields, false, out).map_err(io::Error::from)?,
+        Format::Tsv => write_delimited(tasks, fields, true, out).map_err(io::Error::from)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_records_keep_the_order_of_the_fields() {
+        let tasks = [Task::create_task(1, String::from("a"))];
+        let fields = [Field::State, Field::Name, Field::ParentId, Field::Id];
+        let mut out = Vec::new();
+        write_tasks(&tasks, Format::Json, &fields, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[{\"state\":\"pending\",\"name\":\"a\",\"parent_id\":null,\"id\":1}]\n"
+        );
+    }
+}
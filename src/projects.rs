@@ -0,0 +1,121 @@
+//! Projects group tasks into a hierarchy whose levels are separated by dots,
+//! like `work.infra.ci`. A task in `work.infra.ci` also belongs to
+//! `work.infra` and `work`, so it counts towards their totals as well.
+
+use std::collections::BTreeSet;
+
+use crate::{Task, TaskState};
+
+/// Which tasks the project sidebar narrows the task list to
+#[derive(Clone, PartialEq)]
+pub enum ProjectFilter {
+    All,
+    /// Tasks without a project
+    Unassigned,
+    /// Tasks in the project or any of its subprojects
+    Project(String),
+}
+
+impl ProjectFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            ProjectFilter::All => true,
+            ProjectFilter::Unassigned => task.project.is_none(),
+            ProjectFilter::Project(ancestor) => task
+                .project
+                .as_deref()
+                .is_some_and(|project| contains(ancestor, project)),
+        }
+    }
+
+    /// Project new tasks are added to while the filter is active
+    pub fn project(&self) -> Option<String> {
+        match self {
+            ProjectFilter::Project(project) => Some(project.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `project` is `ancestor` or one of its subprojects
+fn contains(ancestor: &str, project: &str) -> bool {
+    project
+        .strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Trims the levels of a project name and drops empty ones, `None` when
+/// nothing is left
+pub fn normalize(input: &str) -> Option<String> {
+    let levels: Vec<&str> = input
+        .split('.')
+        .map(str::trim)
+        .filter(|level| !level.is_empty())
+        .collect();
+    if levels.is_empty() {
+        None
+    } else {
+        Some(levels.join("."))
+    }
+}
+
+/// A line of the project sidebar
+pub struct ProjectRow {
+    pub filter: ProjectFilter,
+    /// Nesting level, 0 for top-level projects and the special entries
+    pub depth: usize,
+    pub label: String,
+    /// Number of matching tasks in each state, indexed by [`TaskState::index`]
+    pub counts: Vec<usize>,
+}
+
+impl ProjectRow {
+    fn new(tasks: &[Task], filter: ProjectFilter, depth: usize, label: String) -> ProjectRow {
+        let mut counts = vec![0; TaskState::count()];
+        for task in tasks.iter().filter(|task| filter.matches(task)) {
+            counts[task.state.index()] += 1;
+        }
+
+        ProjectRow {
+            filter,
+            depth,
+            label,
+            counts,
+        }
+    }
+}
+
+/// Rows of the project sidebar: all tasks, then every project below its
+/// parent, then the tasks without a project if there are projects at all
+pub fn tree(tasks: &[Task]) -> Vec<ProjectRow> {
+    // Comparing by level keeps subprojects right after their parent
+    let mut projects = BTreeSet::new();
+    for project in tasks.iter().filter_map(|task| task.project.as_deref()) {
+        let levels: Vec<&str> = project.split('.').collect();
+        for depth in 1..=levels.len() {
+            projects.insert(levels[..depth].to_vec());
+        }
+    }
+
+    let mut rows = vec![ProjectRow::new(
+        tasks,
+        ProjectFilter::All,
+        0,
+        String::from("All"),
+    )];
+    for levels in &projects {
+        let filter = ProjectFilter::Project(levels.join("."));
+        let label = levels[levels.len() - 1].to_string();
+        rows.push(ProjectRow::new(tasks, filter, levels.len() - 1, label));
+    }
+    if !projects.is_empty() && tasks.iter().any(|task| task.project.is_none()) {
+        rows.push(ProjectRow::new(
+            tasks,
+            ProjectFilter::Unassigned,
+            0,
+            String::from("(none)"),
+        ));
+    }
+
+    rows
+}
@@ -0,0 +1,919 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::DefaultHasher,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use fs2::FileExt;
+
+use crate::{
+    config::{Backend, Config},
+    Error, Task,
+};
+
+mod location;
+mod schema;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use location::{is_valid_list_name, Location, DEFAULT_LIST};
+use schema::Contents;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+/// Storage for the task list, addressed by task id
+///
+/// Mutations fail with [`Error::DatabaseChanged`] when another process
+/// modified the stored tasks after they were last listed.
+pub trait TaskStore {
+    /// Returns all tasks ordered by id
+    fn list(&self) -> Result<Vec<Task>, Error>;
+
+    fn get(&self, id: usize) -> Result<Option<Task>, Error> {
+        Ok(self.list()?.into_iter().find(|task| task.id == id))
+    }
+
+    /// Stores a new task under the next id and returns that id
+    fn insert(&mut self, task: Task) -> Result<usize, Error>;
+
+    /// Id the next inserted task gets. Ids are never handed out twice, not
+    /// even those of tasks that were deleted since.
+    fn next_id(&self) -> Result<usize, Error>;
+
+    /// Replaces the stored task that has the same id as `task`
+    fn update(&mut self, task: Task) -> Result<(), Error>;
+
+    /// Removes the task with the given id for good
+    fn delete(&mut self, id: usize) -> Result<(), Error> {
+        self.apply_all(vec![Change::Delete(id)])
+    }
+
+    /// Tasks moved out of the list into its archive, ordered by id
+    fn archived(&self) -> Result<Vec<Task>, Error>;
+
+    /// Moves the task with the given id into the archive
+    fn archive(&mut self, id: usize) -> Result<(), Error>;
+
+    /// Moves the task with the given id out of the archive, back into the list
+    fn unarchive(&mut self, id: usize) -> Result<(), Error>;
+
+    /// Makes all of `changes` in one write, so either all of them are stored
+    /// or, when one of them fails, none
+    fn apply_all(&mut self, changes: Vec<Change>) -> Result<(), Error>;
+
+    /// File that changes whenever the stored tasks change, if there is one
+    fn watch_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// A single mutation waiting to be written to a [`TaskStore`]
+#[derive(Clone)]
+pub enum Change {
+    /// Stores a new task under the id [`TaskStore::next_id`] handed out for
+    /// it. When another process got that id meanwhile, the change conflicts.
+    Insert(Task),
+    /// Stores a task under its own id, which must not be taken, to bring
+    /// back a task that was deleted
+    Restore(Task),
+    Update(Task),
+    Delete(usize),
+    Archive(usize),
+    Unarchive(usize),
+}
+
+impl Change {
+    /// Id of the task that is changed
+    pub fn task_id(&self) -> usize {
+        match self {
+            Change::Insert(task) | Change::Restore(task) | Change::Update(task) => task.id,
+            Change::Delete(id) | Change::Archive(id) | Change::Unarchive(id) => *id,
+        }
+    }
+
+    /// Replaces the task id `from` by `to`, also where tasks refer to it
+    pub fn renumber(&mut self, from: usize, to: usize) {
+        let replace = |id: &mut usize| {
+            if *id == from {
+                *id = to;
+            }
+        };
+        match self {
+            Change::Insert(task) | Change::Restore(task) | Change::Update(task) => {
+                replace(&mut task.id);
+                task.parent_id.iter_mut().for_each(replace);
+                task.depends_on.iter_mut().for_each(replace);
+            }
+            Change::Delete(id) | Change::Archive(id) | Change::Unarchive(id) => replace(id),
+        }
+    }
+
+    /// Makes the change to the stored `tasks` and `archived`, taking the id of
+    /// an added task from the id `counter`
+    fn store(
+        self,
+        tasks: &mut Vec<Task>,
+        archived: &mut Vec<Task>,
+        counter: &mut usize,
+    ) -> Result<(), Error> {
+        if let Change::Insert(task) = &self {
+            if task.id < next_id(*counter, tasks, archived) {
+                return Err(Error::DatabaseChanged);
+            }
+            *counter = task.id + 1;
+        }
+        self.apply_to(tasks, archived)
+    }
+
+    /// Makes the change to `tasks` and `archived` directly, keeping the id of
+    /// added tasks
+    pub fn apply_to(self, tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), Error> {
+        match self {
+            Change::Insert(task) | Change::Restore(task) => restore_task(tasks, task),
+            Change::Update(task) => update_task(tasks, task),
+            Change::Delete(id) => delete_task(tasks, id).map(|_| ()),
+            Change::Archive(id) => restore_task(archived, delete_task(tasks, id)?),
+            Change::Unarchive(id) => restore_task(tasks, delete_task(archived, id)?),
+        }
+    }
+}
+
+/// Id the next new task gets: the value of the id `counter`, unless a task
+/// already has that id or a higher one, as in lists from before the counter
+fn next_id(counter: usize, tasks: &[Task], archived: &[Task]) -> usize {
+    tasks
+        .iter()
+        .chain(archived)
+        .map(|task| task.id + 1)
+        .fold(counter.max(1), usize::max)
+}
+
+fn insert_task(
+    tasks: &mut Vec<Task>,
+    counter: &mut usize,
+    archived: &[Task],
+    mut task: Task,
+) -> usize {
+    let id = next_id(*counter, tasks, archived);
+    *counter = id + 1;
+    task.id = id;
+    tasks.push(task);
+    id
+}
+
+fn restore_task(tasks: &mut Vec<Task>, task: Task) -> Result<(), Error> {
+    if contains(tasks, task.id) {
+        return Err(id_taken(task.id));
+    }
+    tasks.push(task);
+    Ok(())
+}
+
+fn contains(tasks: &[Task], id: usize) -> bool {
+    tasks.iter().any(|task| task.id == id)
+}
+
+fn id_taken(id: usize) -> Error {
+    Error::StringError(format!("there already is a task with id {}", id))
+}
+
+fn update_task(tasks: &mut [Task], task: Task) -> Result<(), Error> {
+    let element = tasks
+        .iter_mut()
+        .find(|element| element.id == task.id)
+        .ok_or(Error::TaskNotFound(task.id))?;
+    *element = task;
+    Ok(())
+}
+
+fn delete_task(tasks: &mut Vec<Task>, id: usize) -> Result<Task, Error> {
+    let index = tasks
+        .iter()
+        .position(|task| task.id == id)
+        .ok_or(Error::TaskNotFound(id))?;
+    Ok(tasks.remove(index))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sqlite_unavailable() -> Error {
+    Error::StringError(String::from(
+        "task-tui was built without the `sqlite` feature",
+    ))
+}
+
+fn ensure_db_file_exists(path: &Path) -> Result<File, Error> {
+    let file = {
+        if path.exists() && path.is_file() {
+            OpenOptions::new().read(true).write(true).open(path)?
+        } else {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?
+        }
+    };
+
+    Ok(file)
+}
+
+fn fingerprint(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns `path` with `suffix` appended to its file name
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path
+        .file_name()
+        .map_or_else(OsString::new, |name| name.to_os_string());
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// File next to the DB file at `path` holding the archived tasks of its
+/// list, named so it is never taken for another list
+fn archive_path(path: &Path) -> PathBuf {
+    path.with_extension("archive.json")
+}
+
+fn backup_path(path: &Path, number: usize) -> PathBuf {
+    sibling_path(path, &format!(".bak.{}", number))
+}
+
+/// Flushes the directory entry of `path` so a completed rename survives a crash
+fn sync_parent_dir(path: &Path) -> Result<(), Error> {
+    // A bare file name has an empty parent, the working directory
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Replaces the file at `path` with `contents` without ever leaving a
+/// partially written file behind: the data goes to a temporary file in the
+/// same directory, which is fsynced and then renamed over the original
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let tmp_path = sibling_path(path, ".tmp");
+    let mut tmp_file = File::create(&tmp_path)?;
+    tmp_file.write_all(contents)?;
+    tmp_file.sync_all()?;
+    drop(tmp_file);
+
+    fs::rename(&tmp_path, path)?;
+    sync_parent_dir(path)
+}
+
+/// Shifts the existing backups of `path` up by one, dropping the oldest, and
+/// copies the current file into the first slot. An empty file, as created
+/// for a new list, is not worth a backup.
+fn rotate_backups(path: &Path, backups: usize) -> Result<(), Error> {
+    if backups == 0 || !fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.len() > 0) {
+        return Ok(());
+    }
+
+    for number in (1..backups).rev() {
+        let from = backup_path(path, number);
+        if from.is_file() {
+            fs::rename(&from, backup_path(path, number + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Keeps the tasks of a list in a JSON file, see [`schema`] for its format.
+/// Archived tasks go into a second file of the same format.
+///
+/// Every mutation holds an advisory lock on a `.lock` file next to the
+/// database, so several processes can share it without losing writes.
+pub struct JsonStore {
+    path: PathBuf,
+    /// Number of previous versions kept next to the file
+    backups: usize,
+    /// Fingerprint of the file contents as last listed or written by us
+    seen: Cell<Option<u64>>,
+}
+
+impl JsonStore {
+    pub fn new(path: PathBuf, backups: usize) -> JsonStore {
+        JsonStore {
+            path,
+            backups,
+            seen: Cell::new(None),
+        }
+    }
+
+    /// Blocks until this process holds the database lock, which is released
+    /// when the returned file is dropped
+    fn lock(&self) -> Result<File, Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let lock_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(sibling_path(&self.path, ".lock"))?;
+        lock_file.lock_exclusive()?;
+        Ok(lock_file)
+    }
+
+    fn read_db(&self) -> Result<(Contents, u64), Error> {
+        let mut db_file = ensure_db_file_exists(&self.path)?;
+        let mut s = String::new();
+        db_file.read_to_string(&mut s)?;
+
+        let mut contents = schema::parse(&s)?;
+        // Files from before the counter get one that continues after their
+        // highest id, which is kept from then on even if that task goes
+        contents.next_id = next_id(contents.next_id, &contents.tasks, &[]);
+        Ok((contents, fingerprint(s.as_bytes())))
+    }
+
+    /// The archive file has no id counter, the ids of its tasks were handed
+    /// out by the DB file
+    fn read_archive(&self) -> Result<Vec<Task>, Error> {
+        match fs::read_to_string(archive_path(&self.path)) {
+            Ok(contents) => Ok(schema::parse(&contents)?.tasks),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_archive(&self, mut tasks: Vec<Task>) -> Result<(), Error> {
+        tasks.sort_by_key(|task| task.id);
+        let contents = schema::serialize(&tasks, None)?;
+        write_atomically(&archive_path(&self.path), &contents)
+    }
+
+    fn write_db(&self, Contents { mut tasks, next_id }: Contents) -> Result<(), Error> {
+        tasks.sort_by_key(|task| task.id);
+        let contents = schema::serialize(&tasks, Some(next_id))?;
+
+        rotate_backups(&self.path, self.backups)?;
+        write_atomically(&self.path, &contents)?;
+        self.seen.set(Some(fingerprint(&contents)));
+        Ok(())
+    }
+
+    /// Applies `change` to the stored tasks while holding the lock, refusing
+    /// to overwrite modifications made by another process
+    fn modify<T>(
+        &self,
+        change: impl FnOnce(&mut Contents) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let _lock = self.lock()?;
+        self.modify_locked(change)
+    }
+
+    /// Like [`JsonStore::modify`], for callers already holding the lock
+    fn modify_locked<T>(
+        &self,
+        change: impl FnOnce(&mut Contents) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let (mut contents, current) = self.read_db()?;
+        if matches!(self.seen.get(), Some(seen) if seen != current) {
+            return Err(Error::DatabaseChanged);
+        }
+
+        let value = change(&mut contents)?;
+        self.write_db(contents)?;
+        Ok(value)
+    }
+
+    /// Replaces the database with its `number`th most recent backup. The
+    /// database itself becomes the most recent backup, so this can be undone.
+    pub fn restore_backup(&self, number: usize) -> Result<(), Error> {
+        let backup = backup_path(&self.path, number);
+        if !backup.is_file() {
+            return Err(Error::StringError(format!(
+                "no backup found at {}",
+                backup.display()
+            )));
+        }
+
+        // Make sure the backup parses before it replaces anything
+        let contents = fs::read_to_string(&backup)?;
+        schema::parse(&contents)?;
+
+        let _lock = self.lock()?;
+        rotate_backups(&self.path, self.backups)?;
+        write_atomically(&self.path, contents.as_bytes())
+    }
+}
+
+impl TaskStore for JsonStore {
+    fn list(&self) -> Result<Vec<Task>, Error> {
+        let (Contents { mut tasks, .. }, current) = self.read_db()?;
+        self.seen.set(Some(current));
+        tasks.sort_by_key(|task| task.id);
+        Ok(tasks)
+    }
+
+    fn insert(&mut self, task: Task) -> Result<usize, Error> {
+        self.modify(|db| {
+            let archived = self.read_archive()?;
+            Ok(insert_task(&mut db.tasks, &mut db.next_id, &archived, task))
+        })
+    }
+
+    fn next_id(&self) -> Result<usize, Error> {
+        let (contents, _) = self.read_db()?;
+        Ok(next_id(
+            contents.next_id,
+            &contents.tasks,
+            &self.read_archive()?,
+        ))
+    }
+
+    fn update(&mut self, task: Task) -> Result<(), Error> {
+        self.modify(|db| update_task(&mut db.tasks, task))
+    }
+
+    fn archived(&self) -> Result<Vec<Task>, Error> {
+        self.read_archive()
+    }
+
+    fn archive(&mut self, id: usize) -> Result<(), Error> {
+        self.apply_all(vec![Change::Archive(id)])
+    }
+
+    fn unarchive(&mut self, id: usize) -> Result<(), Error> {
+        self.apply_all(vec![Change::Unarchive(id)])
+    }
+
+    // The archive file gains tasks before the DB file loses them and loses
+    // tasks after the DB file gained them, so a crash in between leaves a
+    // moved task in both files rather than in neither
+    fn apply_all(&mut self, changes: Vec<Change>) -> Result<(), Error> {
+        let _lock = self.lock()?;
+        let before = self.read_archive()?;
+        let mut archived = before.clone();
+        self.modify_locked(|db| {
+            for change in changes {
+                change.store(&mut db.tasks, &mut archived, &mut db.next_id)?;
+            }
+            if archived.iter().any(|task| !contains(&before, task.id)) {
+                let mut both = archived.clone();
+                both.extend(
+                    before
+                        .iter()
+                        .filter(|task| !contains(&archived, task.id))
+                        .cloned(),
+                );
+                self.write_archive(both)?;
+            }
+            Ok(())
+        })?;
+
+        if before.iter().any(|task| !contains(&archived, task.id)) {
+            self.write_archive(archived)?;
+        }
+        Ok(())
+    }
+
+    fn watch_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// Keeps tasks only for the lifetime of the process, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    tasks: Vec<Task>,
+    archived: Vec<Task>,
+    next_id: usize,
+}
+
+#[cfg(test)]
+impl TaskStore for MemoryStore {
+    fn list(&self) -> Result<Vec<Task>, Error> {
+        Ok(self.tasks.clone())
+    }
+
+    fn insert(&mut self, task: Task) -> Result<usize, Error> {
+        Ok(insert_task(
+            &mut self.tasks,
+            &mut self.next_id,
+            &self.archived,
+            task,
+        ))
+    }
+
+    fn next_id(&self) -> Result<usize, Error> {
+        Ok(next_id(self.next_id, &self.tasks, &self.archived))
+    }
+
+    fn update(&mut self, task: Task) -> Result<(), Error> {
+        update_task(&mut self.tasks, task)
+    }
+
+    fn archived(&self) -> Result<Vec<Task>, Error> {
+        Ok(self.archived.clone())
+    }
+
+    fn archive(&mut self, id: usize) -> Result<(), Error> {
+        let task = delete_task(&mut self.tasks, id)?;
+        restore_task(&mut self.archived, task)
+    }
+
+    fn unarchive(&mut self, id: usize) -> Result<(), Error> {
+        let task = delete_task(&mut self.archived, id)?;
+        restore_task(&mut self.tasks, task)
+    }
+
+    fn apply_all(&mut self, changes: Vec<Change>) -> Result<(), Error> {
+        let mut tasks = self.tasks.clone();
+        let mut archived = self.archived.clone();
+        let mut next_id = self.next_id;
+        for change in changes {
+            change.store(&mut tasks, &mut archived, &mut next_id)?;
+        }
+        self.tasks = tasks;
+        self.archived = archived;
+        self.next_id = next_id;
+        Ok(())
+    }
+}
+
+fn no_location() -> Error {
+    Error::StringError(String::from(
+        "no location for the task database found, pass one with --db",
+    ))
+}
+
+/// Opens the DB file at `path` with the backend selected in the config
+pub fn open_store(config: &Config, path: PathBuf) -> Result<Box<dyn TaskStore>, Error> {
+    match config.backend {
+        Backend::Json => Ok(Box::new(JsonStore::new(path, config.backups))),
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Ok(Box::new(SqliteStore::open(&path)?)),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => Err(sqlite_unavailable()),
+    }
+}
+
+/// Imports the JSON database at `json_path` (or the default location) into
+/// the SQLite database at `sqlite_path` (or the default location) and returns
+/// the number of imported tasks
+#[cfg(feature = "sqlite")]
+pub fn migrate_json_to_sqlite(
+    json_path: Option<PathBuf>,
+    sqlite_path: Option<PathBuf>,
+) -> Result<usize, Error> {
+    let json_path = json_path
+        .or_else(|| Location::resolve(None, Backend::Json).map(|l| l.path(DEFAULT_LIST)))
+        .ok_or_else(no_location)?;
+    let sqlite_path = Location::resolve(sqlite_path, Backend::Sqlite)
+        .map(|l| l.path(DEFAULT_LIST))
+        .ok_or_else(no_location)?;
+
+    let json = JsonStore::new(json_path, 0);
+    SqliteStore::open(&sqlite_path)?.import(&json.list()?, &json.archived()?, json.next_id()?)
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn migrate_json_to_sqlite(
+    _json_path: Option<PathBuf>,
+    _sqlite_path: Option<PathBuf>,
+) -> Result<usize, Error> {
+    Err(sqlite_unavailable())
+}
+
+/// Restores the `number`th most recent backup of the JSON database at `path`
+pub fn restore_backup(config: &Config, path: Option<PathBuf>, number: usize) -> Result<(), Error> {
+    if config.backend != Backend::Json {
+        return Err(Error::StringError(String::from(
+            "backups are only kept for the JSON database",
+        )));
+    }
+
+    let path = path.ok_or_else(no_location)?;
+    JsonStore::new(path, config.backups).restore_backup(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory of its own under the system's temporary directory, removed
+    /// with everything in it when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            let mut path = std::env::temp_dir();
+            path.push(format!(
+                "task-tui-test-{}-{:x}",
+                std::process::id(),
+                rand::random::<u64>()
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn db(&self) -> PathBuf {
+            self.0.join("db.json")
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn task(name: &str) -> Task {
+        Task::create_task(0, name.to_string())
+    }
+
+    fn names(tasks: Vec<Task>) -> Vec<String> {
+        tasks.into_iter().map(|task| task.name).collect()
+    }
+
+    /// Runs the store contract below against the stores `$open` creates in
+    /// a temporary directory
+    macro_rules! store_contract {
+        ($module:ident, $open:expr) => {
+            mod $module {
+                use super::*;
+
+                store_contract!(@tests $open;
+                    insert_hands_out_ids_in_order,
+                    ids_of_deleted_tasks_are_not_reused,
+                    update_replaces_the_task_with_the_same_id,
+                    missing_tasks_are_not_found,
+                    restore_keeps_the_id_unless_it_is_taken,
+                    apply_all_stores_all_changes_or_none,
+                    added_tasks_keep_the_id_they_were_given,
+                    archived_tasks_keep_their_ids
+                );
+            }
+        };
+        (@tests $open:expr; $($test:ident),*) => {
+            $(
+                #[test]
+                fn $test() {
+                    let dir = TempDir::new();
+                    let open: fn(&TempDir) -> Box<dyn TaskStore> = $open;
+                    super::$test(open(&dir).as_mut());
+                }
+            )*
+        };
+    }
+
+    store_contract!(memory, |_| Box::new(MemoryStore::default()));
+    store_contract!(json, |dir| Box::new(JsonStore::new(dir.db(), 3)));
+    #[cfg(feature = "sqlite")]
+    store_contract!(sqlite, |dir| Box::new(
+        SqliteStore::open(&dir.0.join("db.sqlite")).unwrap()
+    ));
+
+    fn insert_hands_out_ids_in_order(store: &mut dyn TaskStore) {
+        assert_eq!(store.next_id().unwrap(), 1);
+        assert_eq!(store.insert(task("a")).unwrap(), 1);
+        assert_eq!(store.insert(task("b")).unwrap(), 2);
+
+        assert_eq!(names(store.list().unwrap()), ["a", "b"]);
+        assert_eq!(store.get(2).unwrap().unwrap().name, "b");
+        assert!(store.get(3).unwrap().is_none());
+    }
+
+    fn ids_of_deleted_tasks_are_not_reused(store: &mut dyn TaskStore) {
+        store.insert(task("a")).unwrap();
+        let id = store.insert(task("b")).unwrap();
+        store.delete(id).unwrap();
+
+        assert!(store.get(id).unwrap().is_none());
+        assert_eq!(store.insert(task("c")).unwrap(), id + 1);
+    }
+
+    fn update_replaces_the_task_with_the_same_id(store: &mut dyn TaskStore) {
+        let id = store.insert(task("a")).unwrap();
+        let mut updated = store.get(id).unwrap().unwrap();
+        updated.rename(String::from("renamed"));
+        store.update(updated).unwrap();
+
+        assert_eq!(store.get(id).unwrap().unwrap().name, "renamed");
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    fn missing_tasks_are_not_found(store: &mut dyn TaskStore) {
+        let missing = Task::create_task(7, String::from("missing"));
+
+        assert!(matches!(store.update(missing), Err(Error::TaskNotFound(7))));
+        assert!(matches!(store.delete(7), Err(Error::TaskNotFound(7))));
+        assert!(matches!(store.archive(7), Err(Error::TaskNotFound(7))));
+        assert!(matches!(store.unarchive(7), Err(Error::TaskNotFound(7))));
+    }
+
+    fn restore_keeps_the_id_unless_it_is_taken(store: &mut dyn TaskStore) {
+        let id = store.insert(task("a")).unwrap();
+        let deleted = store.get(id).unwrap().unwrap();
+        store.delete(id).unwrap();
+        store
+            .apply_all(vec![Change::Restore(deleted.clone())])
+            .unwrap();
+
+        assert_eq!(store.get(id).unwrap().unwrap().name, "a");
+        assert!(store.apply_all(vec![Change::Restore(deleted)]).is_err());
+    }
+
+    fn apply_all_stores_all_changes_or_none(store: &mut dyn TaskStore) {
+        let id = store.insert(task("a")).unwrap();
+        let mut renamed = store.get(id).unwrap().unwrap();
+        renamed.rename(String::from("renamed"));
+
+        let failing = vec![
+            Change::Update(renamed.clone()),
+            Change::Insert(Task::create_task(id + 1, String::from("b"))),
+            Change::Delete(id + 5),
+        ];
+        assert!(matches!(
+            store.apply_all(failing),
+            Err(Error::TaskNotFound(_))
+        ));
+        assert_eq!(names(store.list().unwrap()), ["a"]);
+        assert_eq!(store.next_id().unwrap(), id + 1);
+
+        let changes = vec![
+            Change::Update(renamed),
+            Change::Insert(Task::create_task(id + 1, String::from("b"))),
+        ];
+        store.apply_all(changes).unwrap();
+        assert_eq!(names(store.list().unwrap()), ["renamed", "b"]);
+    }
+
+    fn added_tasks_keep_the_id_they_were_given(store: &mut dyn TaskStore) {
+        let id = store.next_id().unwrap();
+        store
+            .apply_all(vec![Change::Insert(Task::create_task(
+                id,
+                String::from("a"),
+            ))])
+            .unwrap();
+        assert_eq!(store.get(id).unwrap().unwrap().name, "a");
+        assert_eq!(store.next_id().unwrap(), id + 1);
+
+        // Another process got the next id first
+        let next = store.next_id().unwrap();
+        store.insert(task("theirs")).unwrap();
+        let mine = Task::create_task(next, String::from("mine"));
+        assert!(matches!(
+            store.apply_all(vec![Change::Insert(mine)]),
+            Err(Error::DatabaseChanged)
+        ));
+        assert_eq!(store.get(next).unwrap().unwrap().name, "theirs");
+    }
+
+    fn archived_tasks_keep_their_ids(store: &mut dyn TaskStore) {
+        let id = store.insert(task("a")).unwrap();
+        store.archive(id).unwrap();
+
+        assert!(store.list().unwrap().is_empty());
+        assert_eq!(store.archived().unwrap()[0].id, id);
+        assert_eq!(store.next_id().unwrap(), id + 1);
+
+        store.unarchive(id).unwrap();
+        assert!(store.archived().unwrap().is_empty());
+        assert_eq!(store.get(id).unwrap().unwrap().name, "a");
+    }
+
+    #[test]
+    fn json_store_refuses_to_overwrite_changes_of_another_process() {
+        let dir = TempDir::new();
+        let mut ours = JsonStore::new(dir.db(), 3);
+        let mut theirs = JsonStore::new(dir.db(), 3);
+        let id = ours.insert(task("a")).unwrap();
+        let mut renamed = ours.list().unwrap().remove(0);
+        renamed.rename(String::from("renamed"));
+
+        theirs.list().unwrap();
+        theirs.insert(task("b")).unwrap();
+        let written = fs::read(dir.db()).unwrap();
+
+        assert!(matches!(
+            ours.apply_all(vec![Change::Update(renamed)]),
+            Err(Error::DatabaseChanged)
+        ));
+        assert_eq!(fs::read(dir.db()).unwrap(), written);
+
+        // Once it has seen their change, it may write again
+        ours.list().unwrap();
+        ours.delete(id).unwrap();
+        assert_eq!(names(theirs.list().unwrap()), ["b"]);
+    }
+
+    /// Number of tasks in the JSON file at `path`
+    fn count(path: &Path) -> usize {
+        schema::parse(&fs::read_to_string(path).unwrap())
+            .unwrap()
+            .tasks
+            .len()
+    }
+
+    #[test]
+    fn backups_rotate_and_drop_the_oldest() {
+        let dir = TempDir::new();
+        let mut store = JsonStore::new(dir.db(), 2);
+
+        // The first write replaces an empty file, which is not backed up
+        store.insert(task("a")).unwrap();
+        assert!(!backup_path(&dir.db(), 1).exists());
+
+        store.insert(task("b")).unwrap();
+        assert_eq!(count(&backup_path(&dir.db(), 1)), 1);
+
+        store.insert(task("c")).unwrap();
+        store.insert(task("d")).unwrap();
+        assert_eq!(count(&dir.db()), 4);
+        assert_eq!(count(&backup_path(&dir.db(), 1)), 3);
+        assert_eq!(count(&backup_path(&dir.db(), 2)), 2);
+        assert!(!backup_path(&dir.db(), 3).exists());
+    }
+
+    #[test]
+    fn restoring_a_backup_backs_up_the_database() {
+        let dir = TempDir::new();
+        let mut store = JsonStore::new(dir.db(), 3);
+        for name in ["a", "b", "c"] {
+            store.insert(task(name)).unwrap();
+        }
+
+        store.restore_backup(2).unwrap();
+        assert_eq!(count(&dir.db()), 1);
+        assert_eq!(count(&backup_path(&dir.db(), 1)), 3);
+        assert_eq!(count(&backup_path(&dir.db(), 2)), 2);
+        assert_eq!(count(&backup_path(&dir.db(), 3)), 1);
+
+        // Which makes restoring undoable
+        store.restore_backup(1).unwrap();
+        assert_eq!(count(&dir.db()), 3);
+        assert!(store.restore_backup(4).is_err());
+    }
+
+    /// Names of the tasks in the JSON file at `path`
+    fn names_in(path: &Path) -> Vec<String> {
+        names(
+            schema::parse(&fs::read_to_string(path).unwrap())
+                .unwrap()
+                .tasks,
+        )
+    }
+
+    #[test]
+    fn json_store_moves_archived_tasks_between_files() {
+        let dir = TempDir::new();
+        let archive = archive_path(&dir.db());
+        let mut store = JsonStore::new(dir.db(), 3);
+        for name in ["a", "b", "c"] {
+            store.insert(task(name)).unwrap();
+        }
+
+        store.archive(1).unwrap();
+        assert_eq!(names_in(&dir.db()), ["b", "c"]);
+        assert_eq!(names_in(&archive), ["a"]);
+
+        // A failing batch touches neither file
+        let written = (fs::read(dir.db()).unwrap(), fs::read(&archive).unwrap());
+        assert!(store
+            .apply_all(vec![Change::Archive(2), Change::Delete(9)])
+            .is_err());
+        assert_eq!(
+            (fs::read(dir.db()).unwrap(), fs::read(&archive).unwrap()),
+            written
+        );
+
+        store
+            .apply_all(vec![Change::Archive(2), Change::Unarchive(1)])
+            .unwrap();
+        assert_eq!(names_in(&dir.db()), ["a", "c"]);
+        assert_eq!(names_in(&archive), ["b"]);
+
+        store.unarchive(2).unwrap();
+        assert_eq!(names_in(&dir.db()), ["a", "b", "c"]);
+        assert!(names_in(&archive).is_empty());
+        assert_eq!(store.next_id().unwrap(), 4);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{config::Backend, Error};
+
+/// Name of the list kept in the main DB file
+pub const DEFAULT_LIST: &str = "default";
+
+/// Directory next to the main DB file holding the other named lists
+const LISTS_DIR: &str = "lists";
+
+/// Location used before task-tui followed the XDG base directories
+const LEGACY_DIR: &str = "./data";
+
+fn extension(backend: Backend) -> &'static str {
+    match backend {
+        Backend::Json => "json",
+        Backend::Sqlite => "sqlite",
+    }
+}
+
+/// `$XDG_DATA_HOME/task-tui`, or `~/.local/share/task-tui` when it is unset
+fn find_data_dir() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home::home_dir().map(|home| home.join(".local/share")))?;
+    Some(data_home.join("task-tui"))
+}
+
+fn find_legacy_db_file(backend: Backend) -> Option<PathBuf> {
+    home::home_dir().map(|mut path| {
+        path.push(LEGACY_DIR);
+        path.push(format!("db.{}", extension(backend)));
+        path
+    })
+}
+
+/// List names end up in file names, so only a safe set of characters is allowed
+pub fn is_valid_list_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Where the task lists of one backend are kept on disk: the default list
+/// in the main DB file, every other list in a file of its own next to it
+pub struct Location {
+    path: PathBuf,
+    backend: Backend,
+}
+
+impl Location {
+    /// Uses `explicit` as the main DB file if given. Otherwise picks the XDG
+    /// data directory, unless a DB file already exists at the legacy location.
+    pub fn resolve(explicit: Option<PathBuf>, backend: Backend) -> Option<Location> {
+        let path = explicit.or_else(|| {
+            let file_name = format!("db.{}", extension(backend));
+            let xdg_path = find_data_dir().map(|dir| dir.join(file_name));
+            match find_legacy_db_file(backend) {
+                Some(legacy) if legacy.is_file() && !xdg_path.iter().any(|p| p.exists()) => {
+                    Some(legacy)
+                }
+                _ => xdg_path,
+            }
+        })?;
+
+        Some(Location { path, backend })
+    }
+
+    fn lists_dir(&self) -> PathBuf {
+        self.path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(LISTS_DIR)
+    }
+
+    /// DB file holding the list called `name`
+    pub fn path(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_LIST {
+            self.path.clone()
+        } else {
+            self.lists_dir()
+                .join(format!("{}.{}", name, extension(self.backend)))
+        }
+    }
+
+    /// Names of all lists, starting with the default one
+    pub fn lists(&self) -> Result<Vec<String>, Error> {
+        let entries = match fs::read_dir(self.lists_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(vec![DEFAULT_LIST.to_string()])
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension() == Some(OsStr::new(extension(self.backend))) {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    if is_valid_list_name(name) && name != DEFAULT_LIST {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names.insert(0, DEFAULT_LIST.to_string());
+
+        Ok(names)
+    }
+}
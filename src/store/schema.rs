@@ -0,0 +1,259 @@
+//! On-disk format of the JSON database.
+//!
+//! The file holds a versioned envelope, `{"version": N, "next_id": N,
+//! "tasks": [...]}`, where `next_id` is left out of archive files.
+//! Older files are upgraded on load by running them through [`MIGRATIONS`]
+//! one version at a time; they are written back in the current format on
+//! the next change.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{history, Error, Task};
+
+/// Version written by this build of task-tui
+pub const CURRENT_VERSION: u64 = 8;
+
+type Migration = fn(Value) -> Result<Value, Error>;
+
+/// `MIGRATIONS[n]` turns a version `n` document into a version `n + 1` one
+const MIGRATIONS: &[Migration] = &[
+    wrap_bare_array,
+    add_priority,
+    add_tags,
+    add_dependencies,
+    add_notes,
+    rename_states,
+    add_history,
+    add_next_id,
+];
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_id: Option<usize>,
+    tasks: &'a [Task],
+}
+
+/// What a DB file holds
+#[derive(Default)]
+pub struct Contents {
+    pub tasks: Vec<Task>,
+    /// Counter the ids of new tasks are taken from, 0 if there is none yet
+    pub next_id: usize,
+}
+
+/// Version 0 was a bare array of tasks, or `null` when there were none
+fn wrap_bare_array(document: Value) -> Result<Value, Error> {
+    let tasks = match document {
+        Value::Null => Value::Array(Vec::new()),
+        tasks => tasks,
+    };
+    Ok(json!({ "version": 1, "tasks": tasks }))
+}
+
+/// Sets `field` to `default` on every task that lacks it and marks the
+/// document as `version`
+fn add_task_field(mut document: Value, version: u64, field: &str, default: Value) -> Value {
+    if let Some(tasks) = document["tasks"].as_array_mut() {
+        for task in tasks.iter_mut().filter_map(Value::as_object_mut) {
+            task.entry(field).or_insert_with(|| default.clone());
+        }
+    }
+    document["version"] = json!(version);
+    document
+}
+
+/// Version 2 gave every task a priority
+fn add_priority(document: Value) -> Result<Value, Error> {
+    Ok(add_task_field(document, 2, "priority", json!("None")))
+}
+
+/// Version 3 gave every task a list of tags
+fn add_tags(document: Value) -> Result<Value, Error> {
+    Ok(add_task_field(document, 3, "tags", json!([])))
+}
+
+/// Version 4 gave every task a list of tasks it depends on
+fn add_dependencies(document: Value) -> Result<Value, Error> {
+    Ok(add_task_field(document, 4, "depends_on", json!([])))
+}
+
+/// Version 5 gave every task notes, empty at first
+fn add_notes(document: Value) -> Result<Value, Error> {
+    Ok(add_task_field(document, 5, "notes", json!("")))
+}
+
+/// Version 6 stores states under their names in the workflow, which for the
+/// built-in states is how they were shown all along
+fn rename_states(mut document: Value) -> Result<Value, Error> {
+    if let Some(tasks) = document["tasks"].as_array_mut() {
+        for task in tasks.iter_mut().filter_map(Value::as_object_mut) {
+            let renamed = match task.get("state").and_then(Value::as_str) {
+                Some("Pending") => "pending",
+                Some("Started") => "started",
+                Some("InProgress") => "in progress",
+                Some("Done") => "done",
+                _ => continue,
+            };
+            task.insert(String::from("state"), json!(renamed));
+        }
+    }
+    document["version"] = json!(6);
+    Ok(document)
+}
+
+/// Version 7 gave every task a history of its state changes, made up from
+/// the timestamps it already had
+fn add_history(document: Value) -> Result<Value, Error> {
+    let mut document = add_task_field(document, 7, "history", json!([]));
+    if let Some(tasks) = document["tasks"].as_array_mut() {
+        for value in tasks.iter_mut() {
+            let mut task: Task = serde_json::from_value(value.take())?;
+            history::backfill(&mut task);
+            *value = serde_json::to_value(task)?;
+        }
+    }
+    Ok(document)
+}
+
+/// Version 8 keeps the counter the ids of new tasks are taken from in
+/// `next_id`. Files without it continue after their highest id.
+fn add_next_id(mut document: Value) -> Result<Value, Error> {
+    document["version"] = json!(8);
+    Ok(document)
+}
+
+fn version_of(document: &Value) -> Result<u64, Error> {
+    match document {
+        Value::Null | Value::Array(_) => Ok(0),
+        Value::Object(envelope) => envelope
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| Error::StringError(String::from("the DB file has no valid version"))),
+        _ => Err(Error::StringError(String::from(
+            "the DB file is not a task database",
+        ))),
+    }
+}
+
+/// Reads a DB file of any supported version
+pub fn parse(s: &str) -> Result<Contents, Error> {
+    let mut document: Value = match serde_json::from_str(s) {
+        Ok(document) => document,
+        Err(e) if e.is_eof() => return Ok(Contents::default()),
+        Err(e) => Err(e)?,
+    };
+
+    let version = version_of(&document)?;
+    if version > CURRENT_VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: version,
+            supported: CURRENT_VERSION,
+        });
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        document = migration(document)?;
+    }
+
+    Ok(Contents {
+        tasks: serde_json::from_value(document["tasks"].take())?,
+        next_id: document["next_id"].as_u64().unwrap_or_default() as usize,
+    })
+}
+
+/// Writes the tasks in the current format, along with the id counter if
+/// the file keeps one
+pub fn serialize(tasks: &[Task], next_id: Option<usize>) -> Result<Vec<u8>, Error> {
+    Ok(serde_json::to_vec(&Envelope {
+        version: CURRENT_VERSION,
+        next_id,
+        tasks,
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V0_TASK: &str = r#"{
+        "id": 3,
+        "name": "old",
+        "state": "InProgress",
+        "created_at": "2021-04-01T10:00:00Z",
+        "started_at": "2021-04-02T10:00:00Z",
+        "finished_at": null
+    }"#;
+
+    #[test]
+    fn upgrades_a_bare_array() {
+        let contents = parse(&format!("[{}]", V0_TASK)).unwrap();
+
+        let task = &contents.tasks[0];
+        assert_eq!(task.id, 3);
+        assert_eq!(task.state.to_string(), "in progress");
+        assert!(matches!(task.priority, crate::Priority::None));
+        assert!(task.tags.is_empty() && task.depends_on.is_empty() && task.notes.is_empty());
+        assert_eq!(task.history.len(), 1);
+        assert_eq!(contents.next_id, 0);
+    }
+
+    #[test]
+    fn upgrades_null_and_empty_files_to_no_tasks() {
+        assert!(parse("null").unwrap().tasks.is_empty());
+        assert!(parse("").unwrap().tasks.is_empty());
+    }
+
+    #[test]
+    fn upgrades_an_envelope_of_an_older_version() {
+        let document = r#"{"version": 5, "tasks": [{
+            "id": 3,
+            "name": "old",
+            "state": "InProgress",
+            "created_at": "2021-04-01T10:00:00Z",
+            "started_at": "2021-04-02T10:00:00Z",
+            "finished_at": null,
+            "priority": "High",
+            "tags": ["work"],
+            "depends_on": [],
+            "notes": ""
+        }]}"#;
+        let task = &parse(document).unwrap().tasks[0];
+
+        assert_eq!(task.state.to_string(), "in progress");
+        assert!(matches!(task.priority, crate::Priority::High));
+        assert_eq!(task.tags, ["work"]);
+        assert_eq!(task.history.len(), 1);
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let tasks = parse(&format!("[{}]", V0_TASK)).unwrap().tasks;
+        let written = serialize(&tasks, Some(7)).unwrap();
+        let contents = parse(std::str::from_utf8(&written).unwrap()).unwrap();
+
+        assert_eq!(contents.tasks.len(), 1);
+        assert_eq!(contents.tasks[0].name, "old");
+        assert_eq!(contents.next_id, 7);
+    }
+
+    #[test]
+    fn refuses_files_from_a_newer_version() {
+        let document = format!(r#"{{"version": {}, "tasks": []}}"#, CURRENT_VERSION + 1);
+
+        assert!(matches!(
+            parse(&document),
+            Err(Error::UnsupportedVersion { found, supported })
+                if found == CURRENT_VERSION + 1 && supported == CURRENT_VERSION
+        ));
+    }
+
+    #[test]
+    fn refuses_files_that_are_no_task_database() {
+        assert!(parse("42").is_err());
+        assert!(parse(r#"{"tasks": []}"#).is_err());
+        assert!(parse("[}").is_err());
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{params, types::Type, Connection, OptionalExtension, Row};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{id_taken, Change, TaskStore};
+use crate::{history, Error, Priority, Task, TaskState};
+
+/// Statements bringing the database schema up to date, where `MIGRATIONS[n]`
+/// upgrades a database with `user_version` n to n + 1. Databases created
+/// before the schema was versioned have version 0 and an up to date table
+/// from the first migration, which is why it must stay idempotent.
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE IF NOT EXISTS tasks (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    state TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    started_at TEXT,
+    finished_at TEXT
+);
+CREATE INDEX IF NOT EXISTS tasks_state ON tasks (state);
+CREATE INDEX IF NOT EXISTS tasks_created_at ON tasks (created_at);
+CREATE INDEX IF NOT EXISTS tasks_started_at ON tasks (started_at);
+CREATE INDEX IF NOT EXISTS tasks_finished_at ON tasks (finished_at);
+",
+    "
+ALTER TABLE tasks ADD COLUMN due_at TEXT;
+CREATE INDEX tasks_due_at ON tasks (due_at);
+",
+    "
+ALTER TABLE tasks ADD COLUMN priority TEXT NOT NULL DEFAULT 'none';
+CREATE INDEX tasks_priority ON tasks (priority);
+",
+    "
+ALTER TABLE tasks ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';
+",
+    "
+ALTER TABLE tasks ADD COLUMN project TEXT;
+CREATE INDEX tasks_project ON tasks (project);
+",
+    "
+ALTER TABLE tasks ADD COLUMN parent_id INTEGER;
+CREATE INDEX tasks_parent_id ON tasks (parent_id);
+",
+    "
+ALTER TABLE tasks ADD COLUMN depends_on TEXT NOT NULL DEFAULT '[]';
+",
+    "
+ALTER TABLE tasks ADD COLUMN notes TEXT NOT NULL DEFAULT '';
+",
+    "
+ALTER TABLE tasks ADD COLUMN history TEXT NOT NULL DEFAULT '[]';
+",
+    "
+ALTER TABLE tasks ADD COLUMN deleted_at TEXT;
+",
+    "
+CREATE TABLE archive (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    state TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    started_at TEXT,
+    finished_at TEXT,
+    due_at TEXT,
+    priority TEXT NOT NULL DEFAULT 'none',
+    tags TEXT NOT NULL DEFAULT '[]',
+    project TEXT,
+    parent_id INTEGER,
+    depends_on TEXT NOT NULL DEFAULT '[]',
+    notes TEXT NOT NULL DEFAULT '',
+    history TEXT NOT NULL DEFAULT '[]',
+    deleted_at TEXT
+);
+",
+    "
+CREATE TABLE metadata (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+INSERT INTO metadata (key, value)
+SELECT 'next_id', COALESCE(MAX(id), 0) + 1
+FROM (SELECT id FROM tasks UNION ALL SELECT id FROM archive);
+",
+];
+
+/// Id the next new task gets: the counter in the metadata, unless a task
+/// already has that id or a higher one
+const NEXT_ID: &str = "SELECT MAX(value, (SELECT COALESCE(MAX(id), 0) + 1
+                        FROM (SELECT id FROM tasks UNION ALL SELECT id FROM archive)))
+                       FROM metadata WHERE key = 'next_id'";
+
+const COLUMNS: &str = "id, name, state, created_at, started_at, finished_at, due_at, priority, \
+                       tags, project, parent_id, depends_on, notes, history, deleted_at";
+
+/// Applies the migrations the database at `conn` is missing
+fn migrate(conn: &mut Connection) -> Result<(), Error> {
+    let version: u64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let supported = MIGRATIONS.len() as u64;
+    if version > supported {
+        return Err(Error::UnsupportedVersion {
+            found: version,
+            supported,
+        });
+    }
+
+    let tx = conn.transaction()?;
+    for migration in &MIGRATIONS[version as usize..] {
+        tx.execute_batch(migration)?;
+    }
+    tx.pragma_update(None, "user_version", supported)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Lists are kept as JSON text
+fn to_json<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).expect("lists of plain values serialize")
+}
+
+fn json_column<T: DeserializeOwned>(row: &Row, index: usize) -> rusqlite::Result<T> {
+    let text: String = row.get(index)?;
+    serde_json::from_str(&text)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+}
+
+fn task_from_row(row: &Row) -> rusqlite::Result<Task> {
+    let state: String = row.get(2)?;
+    let state = TaskState::try_from(state.as_str())
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, Type::Text, Box::new(e)))?;
+    let priority: String = row.get(7)?;
+    let priority = Priority::try_from(priority.as_str())
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(7, Type::Text, Box::new(e)))?;
+
+    let mut task = Task {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        state,
+        created_at: row.get(3)?,
+        started_at: row.get(4)?,
+        finished_at: row.get(5)?,
+        due_at: row.get(6)?,
+        priority,
+        tags: json_column(row, 8)?,
+        project: row.get(9)?,
+        parent_id: row.get(10)?,
+        depends_on: json_column(row, 11)?,
+        notes: row.get(12)?,
+        history: json_column(row, 13)?,
+        deleted_at: row.get(14)?,
+    };
+    // Rows from before the history column get one made up from their
+    // timestamps, which is written back with their next change
+    history::backfill(&mut task);
+    Ok(task)
+}
+
+/// Keeps tasks in a SQLite database, one row per task
+pub struct SqliteStore {
+    conn: Connection,
+    path: PathBuf,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<SqliteStore, Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut conn = Connection::open(path)?;
+        migrate(&mut conn)?;
+        Ok(SqliteStore {
+            conn,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Copies `tasks` and the `archived` ones into an empty database, keeping
+    /// their ids, and continues with new ids from `next_id`
+    pub fn import(
+        &mut self,
+        tasks: &[Task],
+        archived: &[Task],
+        next_id: usize,
+    ) -> Result<usize, Error> {
+        let tx = self.conn.transaction()?;
+
+        let existing: usize = tx.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0))?;
+        if existing > 0 {
+            return Err(Error::StringError(String::from(
+                "the SQLite database already contains tasks",
+            )));
+        }
+
+        for task in tasks {
+            insert_with_id(&tx, "tasks", task)?;
+        }
+        for task in archived {
+            insert_with_id(&tx, "archive", task)?;
+        }
+        set_next_id(&tx, next_id)?;
+
+        tx.commit()?;
+        Ok(tasks.len() + archived.len())
+    }
+}
+
+fn get(conn: &Connection, id: usize) -> Result<Option<Task>, Error> {
+    let task = conn
+        .query_row(
+            &format!("SELECT {} FROM tasks WHERE id = ?1", COLUMNS),
+            params![id],
+            task_from_row,
+        )
+        .optional()?;
+    Ok(task)
+}
+
+fn insert(conn: &Connection, task: Task) -> Result<usize, Error> {
+    let id: usize = conn.query_row(NEXT_ID, [], |row| row.get(0))?;
+    insert_with_id(conn, "tasks", &Task { id, ..task })?;
+    set_next_id(conn, id + 1)?;
+    Ok(id)
+}
+
+/// Stores `task` under its own id, which has to be the next one
+fn insert_as_given(conn: &Connection, task: Task) -> Result<(), Error> {
+    let next_id: usize = conn.query_row(NEXT_ID, [], |row| row.get(0))?;
+    if task.id < next_id {
+        return Err(Error::DatabaseChanged);
+    }
+    insert_with_id(conn, "tasks", &task)?;
+    set_next_id(conn, task.id + 1)?;
+    Ok(())
+}
+
+fn restore(conn: &Connection, task: Task) -> Result<(), Error> {
+    if get(conn, task.id)?.is_some() {
+        return Err(id_taken(task.id));
+    }
+    insert_with_id(conn, "tasks", &task)?;
+    Ok(())
+}
+
+fn update(conn: &Connection, task: Task) -> Result<(), Error> {
+    let changed = conn.execute(
+        "UPDATE tasks SET name = ?2, state = ?3, created_at = ?4, started_at = ?5,
+         finished_at = ?6, due_at = ?7, priority = ?8, tags = ?9, project = ?10,
+         parent_id = ?11, depends_on = ?12, notes = ?13, history = ?14,
+         deleted_at = ?15
+         WHERE id = ?1",
+        params![
+            task.id,
+            task.name,
+            task.state.to_string(),
+            task.created_at,
+            task.started_at,
+            task.finished_at,
+            task.due_at,
+            task.priority.to_string(),
+            to_json(&task.tags),
+            task.project,
+            task.parent_id,
+            to_json(&task.depends_on),
+            task.notes,
+            to_json(&task.history),
+            task.deleted_at,
+        ],
+    )?;
+
+    if changed == 0 {
+        return Err(Error::TaskNotFound(task.id));
+    }
+    Ok(())
+}
+
+fn delete(conn: &Connection, id: usize) -> Result<Task, Error> {
+    let task = get(conn, id)?.ok_or(Error::TaskNotFound(id))?;
+    conn.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
+    Ok(task)
+}
+
+/// Moves the row of the task with the given id from the table `from` to the
+/// table `to`
+fn move_task(conn: &Connection, id: usize, from: &str, to: &str) -> Result<(), Error> {
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {} WHERE id = ?1",
+            to, COLUMNS, COLUMNS, from
+        ),
+        params![id],
+    )?;
+    let moved = conn.execute(&format!("DELETE FROM {} WHERE id = ?1", from), params![id])?;
+    if moved == 0 {
+        return Err(Error::TaskNotFound(id));
+    }
+    Ok(())
+}
+
+fn apply(conn: &Connection, change: Change) -> Result<(), Error> {
+    match change {
+        Change::Insert(task) => insert_as_given(conn, task),
+        Change::Restore(task) => restore(conn, task),
+        Change::Update(task) => update(conn, task),
+        Change::Delete(id) => delete(conn, id).map(|_| ()),
+        Change::Archive(id) => move_task(conn, id, "tasks", "archive"),
+        Change::Unarchive(id) => move_task(conn, id, "archive", "tasks"),
+    }
+}
+
+fn set_next_id(conn: &Connection, next_id: usize) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE metadata SET value = ?1 WHERE key = 'next_id'",
+        params![next_id],
+    )
+}
+
+fn insert_with_id(conn: &Connection, table: &str, task: &Task) -> rusqlite::Result<usize> {
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            table, COLUMNS
+        ),
+        params![
+            task.id,
+            task.name,
+            task.state.to_string(),
+            task.created_at,
+            task.started_at,
+            task.finished_at,
+            task.due_at,
+            task.priority.to_string(),
+            to_json(&task.tags),
+            task.project,
+            task.parent_id,
+            to_json(&task.depends_on),
+            task.notes,
+            to_json(&task.history),
+            task.deleted_at,
+        ],
+    )
+}
+
+impl TaskStore for SqliteStore {
+    fn list(&self) -> Result<Vec<Task>, Error> {
+        let mut statement = self
+            .conn
+            .prepare(&format!("SELECT {} FROM tasks ORDER BY id", COLUMNS))?;
+        let tasks = statement
+            .query_map([], task_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tasks)
+    }
+
+    fn get(&self, id: usize) -> Result<Option<Task>, Error> {
+        get(&self.conn, id)
+    }
+
+    fn insert(&mut self, task: Task) -> Result<usize, Error> {
+        let tx = self.conn.transaction()?;
+        let id = insert(&tx, task)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    fn next_id(&self) -> Result<usize, Error> {
+        Ok(self.conn.query_row(NEXT_ID, [], |row| row.get(0))?)
+    }
+
+    fn update(&mut self, task: Task) -> Result<(), Error> {
+        update(&self.conn, task)
+    }
+
+    fn archived(&self) -> Result<Vec<Task>, Error> {
+        let mut statement = self
+            .conn
+            .prepare(&format!("SELECT {} FROM archive ORDER BY id", COLUMNS))?;
+        let tasks = statement
+            .query_map([], task_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tasks)
+    }
+
+    fn archive(&mut self, id: usize) -> Result<(), Error> {
+        self.apply_all(vec![Change::Archive(id)])
+    }
+
+    fn unarchive(&mut self, id: usize) -> Result<(), Error> {
+        self.apply_all(vec![Change::Unarchive(id)])
+    }
+
+    fn apply_all(&mut self, changes: Vec<Change>) -> Result<(), Error> {
+        let tx = self.conn.transaction()?;
+        for change in changes {
+            apply(&tx, change)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn watch_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
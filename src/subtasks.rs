@@ -0,0 +1,179 @@
+//! Tasks are split into subtasks by pointing the `parent_id` of the subtasks
+//! at them.
+//!
+//! When a task is completed, all of its unfinished subtasks are completed
+//! along with it, which is refused while one of them is blocked by a task
+//! outside of them. When a task is deleted, its subtasks move up to its own
+//! parent instead of being deleted too.
+
+use std::collections::HashSet;
+
+use crate::{dependencies, Error, Task};
+
+/// A line of the task tree
+pub struct TreeRow<'a> {
+    pub task: &'a Task,
+    /// Number of ancestors shown above the task
+    pub depth: usize,
+    /// Whether subtasks of the task are shown, or would be when expanded
+    pub has_children: bool,
+    /// Number of finished and of all direct subtasks, `None` without subtasks
+    pub progress: Option<(usize, usize)>,
+    /// Whether the task waits for one of its dependencies
+    pub blocked: bool,
+}
+
+/// Number of finished and of all direct subtasks of the task with the given id
+pub fn progress(tasks: &[Task], id: usize) -> Option<(usize, usize)> {
+    let children: Vec<_> = tasks
+        .iter()
+        .filter(|task| task.parent_id == Some(id))
+        .collect();
+    if children.is_empty() {
+        return None;
+    }
+
+    let done = children
+        .iter()
+        .filter(|task| task.state.is_finished())
+        .count();
+    Some((done, children.len()))
+}
+
+/// Ids of the subtasks of the task with the given id, their subtasks and so on
+pub fn descendants(tasks: &[Task], id: usize) -> Vec<usize> {
+    let mut found = vec![id];
+    let mut next = 0;
+    while next < found.len() {
+        let parent = found[next];
+        for task in tasks.iter().filter(|task| task.parent_id == Some(parent)) {
+            if !found.contains(&task.id) {
+                found.push(task.id);
+            }
+        }
+        next += 1;
+    }
+    found.remove(0);
+    found
+}
+
+/// Arranges `visible` as a tree, keeping their order among siblings. Tasks
+/// whose parent is not visible are shown at the top level, subtasks of the
+/// tasks in `collapsed` are left out.
+pub fn flatten<'a>(
+    all: &[Task],
+    visible: &[&'a Task],
+    collapsed: &HashSet<usize>,
+) -> Vec<TreeRow<'a>> {
+    let ids: HashSet<usize> = visible.iter().map(|task| task.id).collect();
+
+    let mut tree = Tree {
+        all,
+        visible,
+        collapsed,
+        placed: HashSet::new(),
+        rows: Vec::with_capacity(visible.len()),
+    };
+
+    let roots = visible
+        .iter()
+        .filter(|task| task.parent_id.is_none_or(|parent| !ids.contains(&parent)));
+    for root in roots {
+        tree.add(root, 0, true);
+    }
+
+    // Only tasks caught in a cycle of parents are left, show them on their own
+    for task in visible {
+        if !tree.placed.contains(&task.id) {
+            tree.add(task, 0, true);
+        }
+    }
+
+    tree.rows
+}
+
+/// State of [`flatten`] while it walks the tasks
+struct Tree<'a, 'b> {
+    all: &'b [Task],
+    visible: &'b [&'a Task],
+    collapsed: &'b HashSet<usize>,
+    /// Tasks that were already walked, shown or not
+    placed: HashSet<usize>,
+    rows: Vec<TreeRow<'a>>,
+}
+
+impl<'a> Tree<'a, '_> {
+    /// Walks `task` and its subtasks, adding rows for them if `shown`
+    fn add(&mut self, task: &'a Task, depth: usize, shown: bool) {
+        if !self.placed.insert(task.id) {
+            return;
+        }
+
+        let children: Vec<&'a Task> = self
+            .visible
+            .iter()
+            .filter(|child| child.parent_id == Some(task.id) && !self.placed.contains(&child.id))
+            .copied()
+            .collect();
+        if shown {
+            self.rows.push(TreeRow {
+                task,
+                depth,
+                has_children: !children.is_empty(),
+                progress: progress(self.all, task.id),
+                blocked: dependencies::is_blocked(self.all, task),
+            });
+        }
+
+        let expanded = shown && !self.collapsed.contains(&task.id);
+        for child in children {
+            self.add(child, depth + 1, expanded);
+        }
+    }
+}
+
+/// Completes the unfinished subtasks of the task with the given id, at any
+/// depth, and returns them
+pub fn complete_descendants(tasks: &mut [Task], id: usize) -> Result<Vec<Task>, Error> {
+    let descendants = descendants(tasks, id);
+
+    // Tasks completed together do not block each other
+    for task in tasks.iter().filter(|task| descendants.contains(&task.id)) {
+        let blockers: Vec<usize> = dependencies::blockers(tasks, task)
+            .into_iter()
+            .filter(|blocker| *blocker != id && !descendants.contains(blocker))
+            .collect();
+        if task.state.is_initial() && !blockers.is_empty() {
+            return Err(Error::Blocked {
+                id: task.id,
+                blockers,
+            });
+        }
+    }
+
+    let mut changed = Vec::new();
+    for task in tasks
+        .iter_mut()
+        .filter(|task| descendants.contains(&task.id) && !task.state.is_finished())
+    {
+        task.complete();
+        changed.push(task.clone());
+    }
+    Ok(changed)
+}
+
+/// Moves the direct subtasks of the task with the given id to its parent,
+/// before it gets deleted, and returns them
+pub fn promote_children(tasks: &mut [Task], id: usize) -> Vec<Task> {
+    let parent_id = tasks
+        .iter()
+        .find(|task| task.id == id)
+        .and_then(|task| task.parent_id);
+
+    let mut changed = Vec::new();
+    for task in tasks.iter_mut().filter(|task| task.parent_id == Some(id)) {
+        task.parent_id = parent_id;
+        changed.push(task.clone());
+    }
+    changed
+}
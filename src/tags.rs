@@ -0,0 +1,22 @@
+//! Tags are entered and shown as words like `+backend +release`, the `+` in
+//! front of each one being optional when typing them.
+
+/// Splits `input` at whitespace and commas into tags, dropping the `+`
+/// markers and duplicates
+pub fn parse(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in input.split(|c: char| c.is_whitespace() || c == ',') {
+        let tag = word.trim_start_matches('+');
+        if !tag.is_empty() && !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+pub fn format(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("+{}", tag))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
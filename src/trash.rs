@@ -0,0 +1,44 @@
+//! Deleting a task moves it to the trash by setting its `deleted_at`. It
+//! stays in the store but is left out everywhere else until it is restored
+//! or purged for good, which happens on its own after `trash_days` days.
+//!
+//! A task in the trash is cut off from the others like a deleted one: its
+//! subtasks move up a level and tasks depending on it are unblocked.
+
+use chrono::{DateTime, Utc};
+
+use crate::{dates, dependencies, subtasks, Task};
+
+/// Moves the task with the given id among `tasks` to the trash and returns
+/// it along with the other tasks that changed
+pub fn trash(tasks: &mut [Task], id: usize) -> Vec<Task> {
+    let mut changed = subtasks::promote_children(tasks, id);
+    changed.extend(dependencies::remove_dependency(tasks, id));
+    if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+        task.deleted_at = Some(Utc::now());
+        changed.push(task.clone());
+    }
+    changed
+}
+
+/// Takes `task` out of the trash. Its parent and dependencies are dropped
+/// unless they are among the `live` tasks.
+pub fn restore(live: &[Task], task: &mut Task) {
+    let exists = |id: &usize| live.iter().any(|other| other.id == *id);
+    task.deleted_at = None;
+    task.parent_id = task.parent_id.filter(exists);
+    task.depends_on.retain(exists);
+}
+
+/// Ids of the tasks in `trash` that were deleted more than `days` days
+/// before `now`
+pub fn expired(trash: &[Task], days: u64, now: DateTime<Utc>) -> Vec<usize> {
+    trash
+        .iter()
+        .filter(|task| {
+            task.deleted_at
+                .is_some_and(|at| dates::expired(at, days, now))
+        })
+        .map(|task| task.id)
+        .collect()
+}
@@ -0,0 +1,183 @@
+//! Changes made in the app can be undone and redone for as long as it runs.
+//! Every action is one step, even when it changes several tasks, like
+//! deleting a task that has subtasks or completing a whole tree.
+
+use crate::{store::Change, Task};
+
+/// One action, along with the changes that revert it and the ones that make
+/// it again
+struct Step {
+    description: String,
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+#[derive(Default)]
+pub struct UndoHistory {
+    done: Vec<Step>,
+    undone: Vec<Step>,
+}
+
+impl UndoHistory {
+    /// Remembers `changes`, made to the tasks as they were `before`, as the
+    /// latest step. Steps that were undone cannot be redone after that.
+    pub fn record(&mut self, before: &[Task], changes: &[Change]) {
+        let find = |id: usize| before.iter().find(|task| task.id == id).cloned();
+        let mut undo: Vec<Change> = changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::Insert(task) | Change::Restore(task) => Some(Change::Delete(task.id)),
+                Change::Update(task) => find(task.id).map(Change::Update),
+                Change::Delete(id) => find(*id).map(Change::Restore),
+                Change::Archive(id) => Some(Change::Unarchive(*id)),
+                Change::Unarchive(id) => Some(Change::Archive(*id)),
+            })
+            .collect();
+        undo.reverse();
+        // Added tasks have to come back under the id they were shown with
+        let redo = changes
+            .iter()
+            .map(|change| match change {
+                Change::Insert(task) => Change::Restore(task.clone()),
+                change => change.clone(),
+            })
+            .collect();
+
+        self.done.push(Step {
+            description: describe(before, changes),
+            undo,
+            redo,
+        });
+        self.undone.clear();
+    }
+
+    /// Moves the latest step to the ones that can be redone and returns what
+    /// it did and the changes reverting it
+    pub fn undo(&mut self) -> Option<(String, Vec<Change>)> {
+        let step = self.done.pop()?;
+        let result = (step.description.clone(), step.undo.clone());
+        self.undone.push(step);
+        Some(result)
+    }
+
+    /// Moves the latest undone step back and returns what it did and the
+    /// changes making it again
+    pub fn redo(&mut self) -> Option<(String, Vec<Change>)> {
+        let step = self.undone.pop()?;
+        let result = (step.description.clone(), step.redo.clone());
+        self.done.push(step);
+        Some(result)
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+}
+
+fn describe(before: &[Task], changes: &[Change]) -> String {
+    let was_deleted = |id: usize| before.iter().any(|task| task.id == id && task.is_deleted());
+    let description = changes.iter().find_map(|change| match change {
+        Change::Delete(id) => Some(format!("purging task {}", id)),
+        Change::Update(task) if task.is_deleted() && !was_deleted(task.id) => {
+            Some(format!("moving task {} to the trash", task.id))
+        }
+        Change::Update(task) if !task.is_deleted() && was_deleted(task.id) => {
+            Some(format!("restoring task {}", task.id))
+        }
+        Change::Insert(task) | Change::Restore(task) => Some(format!("adding task {}", task.id)),
+        Change::Archive(id) => Some(format!("archiving task {}", id)),
+        Change::Unarchive(id) => Some(format!("unarchiving task {}", id)),
+        Change::Update(_) => None,
+    });
+
+    description.unwrap_or_else(|| match changes {
+        [Change::Update(task)] => format!("changing task {}", task.id),
+        _ => format!("changing {} tasks", changes.len()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::{MemoryStore, TaskStore},
+        trash,
+    };
+
+    /// Makes `changes` to `store` and records them in `history`
+    fn apply(store: &mut MemoryStore, history: &mut UndoHistory, changes: Vec<Change>) {
+        let before = store.list().unwrap();
+        store.apply_all(changes.clone()).unwrap();
+        history.record(&before, &changes);
+    }
+
+    #[test]
+    fn undoing_an_add_deletes_the_task_and_redoing_brings_it_back() {
+        let mut store = MemoryStore::default();
+        let mut history = UndoHistory::default();
+        let id = store.next_id().unwrap();
+        let task = Task::create_task(id, String::from("a"));
+        apply(&mut store, &mut history, vec![Change::Insert(task)]);
+
+        let (description, changes) = history.undo().unwrap();
+        assert_eq!(description, format!("adding task {}", id));
+        store.apply_all(changes).unwrap();
+        assert!(store.list().unwrap().is_empty());
+
+        let (_, changes) = history.redo().unwrap();
+        store.apply_all(changes).unwrap();
+        assert_eq!(store.get(id).unwrap().unwrap().name, "a");
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn undoing_a_trash_restores_subtasks_and_dependencies() {
+        let mut store = MemoryStore::default();
+        let mut history = UndoHistory::default();
+        let parent = store
+            .insert(Task::create_task(0, String::from("parent")))
+            .unwrap();
+        let mut child = Task::create_task(0, String::from("child"));
+        child.parent_id = Some(parent);
+        let child = store.insert(child).unwrap();
+        let mut dependent = Task::create_task(0, String::from("dependent"));
+        dependent.depends_on = vec![parent];
+        let dependent = store.insert(dependent).unwrap();
+
+        let mut tasks = store.list().unwrap();
+        let changed = trash::trash(&mut tasks, parent);
+        assert_eq!(changed.len(), 3);
+        apply(
+            &mut store,
+            &mut history,
+            changed.into_iter().map(Change::Update).collect(),
+        );
+        assert_eq!(store.get(child).unwrap().unwrap().parent_id, None);
+        assert!(store.get(dependent).unwrap().unwrap().depends_on.is_empty());
+
+        let (description, changes) = history.undo().unwrap();
+        assert_eq!(description, format!("moving task {} to the trash", parent));
+        store.apply_all(changes).unwrap();
+        assert!(!store.get(parent).unwrap().unwrap().is_deleted());
+        assert_eq!(store.get(child).unwrap().unwrap().parent_id, Some(parent));
+        assert_eq!(store.get(dependent).unwrap().unwrap().depends_on, [parent]);
+    }
+
+    #[test]
+    fn a_new_step_drops_the_undone_ones() {
+        let mut store = MemoryStore::default();
+        let mut history = UndoHistory::default();
+        let id = store.next_id().unwrap();
+        let task = Task::create_task(id, String::from("a"));
+        apply(&mut store, &mut history, vec![Change::Insert(task)]);
+        let (_, changes) = history.undo().unwrap();
+        store.apply_all(changes).unwrap();
+
+        let task = Task::create_task(id + 1, String::from("b"));
+        apply(&mut store, &mut history, vec![Change::Insert(task)]);
+        assert!(history.redo().is_none());
+        assert!(history.undo().is_some());
+        assert!(history.undo().is_none());
+    }
+}
@@ -0,0 +1,45 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use notify::{recommended_watcher, Config, Event, PollWatcher, RecursiveMode, Result, Watcher};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub type FileWatcher = Box<dyn Watcher + Send>;
+
+/// Calls `on_change` whenever the file at `path` is written, replaced or removed.
+///
+/// The parent directory is watched rather than the file itself, because
+/// atomic writes replace the file with a new one. Falls back to polling when
+/// the platform's native notifications are not available.
+pub fn watch_file(
+    path: &Path,
+    on_change: impl Fn() + Send + Sync + 'static,
+) -> Result<FileWatcher> {
+    let file = path.to_path_buf();
+    let on_change = Arc::new(on_change);
+    let handler = move |event: Result<Event>| {
+        if let Ok(event) = event {
+            let relevant =
+                event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove();
+            if relevant && event.paths.iter().any(|changed| changed == &file) {
+                on_change();
+            }
+        }
+    };
+    let dir = path.parent().unwrap_or(path);
+
+    let native = recommended_watcher(handler.clone()).and_then(|mut watcher| {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    });
+
+    match native {
+        Ok(watcher) => Ok(Box::new(watcher)),
+        Err(_) => {
+            let mut watcher =
+                PollWatcher::new(handler, Config::default().with_poll_interval(POLL_INTERVAL))?;
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            Ok(Box::new(watcher))
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! The states tasks move through are configured as a list in the config
+//! file, for example:
+//!
+//! ```json
+//! "workflow": [
+//!     {"name": "pending"},
+//!     {"name": "in progress", "started": true, "aliases": ["started"], "color": "yellow"},
+//!     {"name": "review", "started": true, "next": "done", "color": "magenta"},
+//!     {"name": "blocked", "next": "in progress", "color": "red"},
+//!     {"name": "done", "finished": true, "color": "green"},
+//!     {"name": "cancelled", "finished": true, "color": "darkgray"}
+//! ]
+//! ```
+//!
+//! New tasks start in the first state. Progressing a task moves it to the
+//! `next` state, which defaults to the one after it unless the state is
+//! finished. Entering a `started` state records when the task was started,
+//! entering a `finished` one when it was finished. Without this setting the
+//! four built-in states pending, started, in progress and done are used.
+//! Tasks stored under one of those names still load with a workflow that
+//! doesn't define it: pending becomes the first state, started and in progress
+//! the first started state and done the first finished state.
+//!
+//! The workflow is set up once on startup and shared by everything that
+//! looks at task states.
+
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tui::style::Color;
+
+use crate::Error;
+
+static CURRENT: OnceLock<Workflow> = OnceLock::new();
+
+/// Checks `workflow` and makes it the one used from now on
+pub fn install(workflow: Workflow) -> Result<(), Error> {
+    workflow.validate()?;
+    CURRENT
+        .set(workflow)
+        .map_err(|_| Error::StringError(String::from("the workflow is already set up")))
+}
+
+/// The installed workflow, or the built-in one if none was installed
+pub fn current() -> &'static Workflow {
+    CURRENT.get_or_init(Workflow::default)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateConfig {
+    pub name: String,
+    /// Other names of the state, so tasks stored with another workflow
+    /// still load, e.g. `started` when that state was merged into this one
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// State progressing a task moves it to
+    #[serde(default)]
+    pub next: Option<String>,
+    /// States a task may be moved to from this one, any of them if not given
+    #[serde(default)]
+    pub transitions: Option<Vec<String>>,
+    /// Whether tasks in this state have been started
+    #[serde(default)]
+    pub started: bool,
+    /// Whether tasks in this state are finished, which also makes them stop
+    /// blocking the tasks that depend on them
+    #[serde(default)]
+    pub finished: bool,
+    /// Colour the state is shown in, a name like `lightgreen` or `#rrggbb`
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+impl StateConfig {
+    fn new(name: &str, started: bool, finished: bool) -> StateConfig {
+        StateConfig {
+            name: name.to_string(),
+            aliases: Vec::new(),
+            next: None,
+            transitions: None,
+            started,
+            finished,
+            color: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(transparent)]
+pub struct Workflow {
+    pub states: Vec<StateConfig>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Workflow {
+            states: vec![
+                StateConfig::new("pending", false, false),
+                StateConfig::new("started", true, false),
+                StateConfig::new("in progress", true, false),
+                StateConfig::new("done", false, true),
+            ],
+        }
+    }
+}
+
+/// Form of a state name that is compared, so `InProgress`, `in-progress`
+/// and `in progress` all name the same state
+fn key(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl Workflow {
+    fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: String| Err(Error::StringError(format!("workflow: {}", message)));
+
+        if self.states.is_empty() {
+            return invalid(String::from("no states are defined"));
+        }
+        if !self.states.iter().any(|state| state.finished) {
+            return invalid(String::from("no state is marked as finished"));
+        }
+
+        let mut keys = Vec::new();
+        for state in &self.states {
+            for name in std::iter::once(&state.name).chain(&state.aliases) {
+                let name_key = key(name);
+                if name_key.is_empty() || keys.contains(&name_key) {
+                    return invalid(format!("the state name '{}' is empty or used twice", name));
+                }
+                keys.push(name_key);
+            }
+        }
+
+        for state in &self.states {
+            let targets = state.next.iter().chain(state.transitions.iter().flatten());
+            for target in targets {
+                if self.find(target).is_none() {
+                    return invalid(format!(
+                        "state '{}' refers to the unknown state '{}'",
+                        state.name, target
+                    ));
+                }
+            }
+            if let Some(color) = &state.color {
+                if parse_color(color).is_none() {
+                    return invalid(format!("unknown colour '{}'", color));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn count(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Position of the state with the given name or alias
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.states
+            .iter()
+            .position(|state| state.name == name)
+            .or_else(|| {
+                let name = key(name);
+                self.states.iter().position(|state| {
+                    key(&state.name) == name || state.aliases.iter().any(|alias| key(alias) == name)
+                })
+            })
+    }
+
+    /// Position of the state a task stored under `name` is in, which is `find`
+    /// with the built-in state names mapped by their role as a fallback
+    pub fn find_stored(&self, name: &str) -> Option<usize> {
+        self.find(name).or_else(|| match key(name).as_str() {
+            "pending" => Some(0),
+            "started" | "inprogress" => self
+                .states
+                .iter()
+                .position(|state| state.started)
+                .or(Some(0)),
+            "done" => self.states.iter().position(|state| state.finished),
+            _ => None,
+        })
+    }
+
+    pub fn state(&self, index: usize) -> &StateConfig {
+        &self.states[index]
+    }
+
+    /// Position of the state progressing moves a task on to from `index`
+    pub fn next(&self, index: usize) -> Option<usize> {
+        let state = self.state(index);
+        match &state.next {
+            Some(next) => self.find(next),
+            None if state.finished => None,
+            None => Some(index + 1).filter(|&next| next < self.count()),
+        }
+    }
+
+    /// Position of the state whose next state is `index`, which is where
+    /// moving a task back takes it
+    pub fn previous(&self, index: usize) -> Option<usize> {
+        (0..self.count()).find(|&other| other != index && self.next(other) == Some(index))
+    }
+
+    /// Whether a task may be moved from the state at `from` to the one at `to`.
+    /// Moving on to the next state is always allowed.
+    pub fn allows(&self, from: usize, to: usize) -> bool {
+        let state = self.state(from);
+        self.next(from) == Some(to)
+            || state.transitions.as_ref().is_none_or(|transitions| {
+                transitions
+                    .iter()
+                    .any(|target| self.find(target) == Some(to))
+            })
+    }
+
+    pub fn color(&self, index: usize) -> Option<Color> {
+        self.state(index).color.as_deref().and_then(parse_color)
+    }
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        let value = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)?;
+        let [_, r, g, b] = value.to_be_bytes();
+        return Some(Color::Rgb(r, g, b));
+    }
+
+    Some(match key(name).as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// todo → doing → review → done, with blocked going back to doing
+    fn custom() -> Workflow {
+        serde_json::from_str(
+            r#"[
+                {"name": "todo"},
+                {"name": "doing", "started": true, "aliases": ["wip"]},
+                {"name": "review", "started": true, "next": "done", "transitions": ["doing"]},
+                {"name": "blocked", "next": "doing"},
+                {"name": "done", "finished": true},
+                {"name": "cancelled", "finished": true}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn find_matches_names_and_aliases_loosely() {
+        let workflow = custom();
+        assert!(workflow.validate().is_ok());
+        assert_eq!(workflow.find("review"), Some(2));
+        assert_eq!(workflow.find("Review"), Some(2));
+        assert_eq!(workflow.find("WIP"), Some(1));
+        assert_eq!(workflow.find("in progress"), None);
+        assert_eq!(Workflow::default().find("InProgress"), Some(2));
+        assert_eq!(Workflow::default().find("in-progress"), Some(2));
+    }
+
+    #[test]
+    fn next_follows_the_list_unless_set_or_finished() {
+        let workflow = custom();
+        assert_eq!(workflow.next(0), Some(1));
+        assert_eq!(workflow.next(1), Some(2));
+        assert_eq!(workflow.next(2), Some(4));
+        assert_eq!(workflow.next(3), Some(1));
+        assert_eq!(workflow.next(4), None);
+        assert_eq!(workflow.next(5), None);
+    }
+
+    #[test]
+    fn previous_is_the_state_whose_next_it_is() {
+        let workflow = custom();
+        assert_eq!(workflow.previous(0), None);
+        assert_eq!(workflow.previous(1), Some(0));
+        assert_eq!(workflow.previous(2), Some(1));
+        assert_eq!(workflow.previous(4), Some(2));
+        assert_eq!(workflow.previous(5), None);
+    }
+
+    #[test]
+    fn allows_next_and_listed_transitions_only() {
+        let workflow = custom();
+        assert!(workflow.allows(2, 4));
+        assert!(workflow.allows(2, 1));
+        assert!(!workflow.allows(2, 0));
+        assert!(!workflow.allows(2, 5));
+        assert!(workflow.allows(0, 5));
+    }
+
+    #[test]
+    fn built_in_names_map_by_role_when_not_defined() {
+        let workflow = custom();
+        assert_eq!(workflow.find_stored("pending"), Some(0));
+        assert_eq!(workflow.find_stored("started"), Some(1));
+        assert_eq!(workflow.find_stored("in progress"), Some(1));
+        assert_eq!(workflow.find_stored("InProgress"), Some(1));
+        assert_eq!(workflow.find_stored("done"), Some(4));
+        assert_eq!(workflow.find_stored("review"), Some(2));
+        assert_eq!(workflow.find_stored("archived"), None);
+    }
+
+    #[test]
+    fn invalid_workflows_are_rejected() {
+        let no_finished: Workflow = serde_json::from_str(r#"[{"name": "todo"}]"#).unwrap();
+        assert!(no_finished.validate().is_err());
+        let unknown_next: Workflow = serde_json::from_str(
+            r#"[{"name": "todo", "next": "nope"}, {"name": "done", "finished": true}]"#,
+        )
+        .unwrap();
+        assert!(unknown_next.validate().is_err());
+        let duplicate: Workflow =
+            serde_json::from_str(r#"[{"name": "done"}, {"name": "Done", "finished": true}]"#)
+                .unwrap();
+        assert!(duplicate.validate().is_err());
+    }
+}